@@ -29,8 +29,8 @@ const INPUT_STORAGE_STR: &str = include_str!("bet-storage.json");
 async fn main() -> Result<()> {
     println!("STORAGE DECODING EXAMPLE");
 
-    install_parser().await;
-    let mut parser = Parser::new();
+    install_parser().await.unwrap();
+    let parser = Parser::new();
 
     let schema = Storage::get_schema()?;
 
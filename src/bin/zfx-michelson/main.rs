@@ -0,0 +1,233 @@
+//! Command-line interface to the conversions
+//!
+//! ```text
+//! zfx-michelson decode --schema schema.json value.json
+//! zfx-michelson encode --schema schema.tz < values.ndjson
+//! zfx-michelson convert --to text value.json
+//! zfx-michelson repl
+//! ```
+//!
+//! The value is read from the file given as argument, otherwise the standard input is read
+//! as NDJSON: one value per line, with one result per line. Schemas and Michelson values
+//! can be written in JSON or in the Michelson notation.
+mod repl;
+
+use clap::{Args, Parser as _, Subcommand, ValueEnum};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use zfx_michelson::micheline::Micheline;
+use zfx_michelson::{install_parser_in, Backend, Codec, Error, NativeCodec, ParserBuilder, Result};
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(clap::Parser)]
+#[command(name = "zfx-michelson", version, about = "Michelson decoder/encoder")]
+struct Cli {
+    /// The conversion backend: `node` (Taquito) or `native`
+    #[arg(long, global = true, default_value = "node")]
+    backend: Backend,
+    /// Pretty-print the JSON output
+    #[arg(long, global = true)]
+    pretty: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Convert(ConvertCommand),
+    /// Load a schema and convert values interactively, see `:help`
+    Repl,
+}
+
+#[derive(Subcommand)]
+enum ConvertCommand {
+    /// Decode Michelson values to Taquito-style JSON
+    Decode(Conversion),
+    /// Encode Taquito-style JSON to Michelson values
+    Encode {
+        #[command(flatten)]
+        conversion: Conversion,
+        /// Print the Michelson notation instead of JSON
+        #[arg(long)]
+        text: bool,
+    },
+    /// Convert Michelson expressions between JSON and the Michelson notation
+    Convert {
+        /// The output notation
+        #[arg(long, value_enum)]
+        to: Notation,
+        /// File containing a single expression
+        input: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct Conversion {
+    /// File containing the schema (a Michelson type)
+    #[arg(long, short)]
+    schema: PathBuf,
+    /// File containing a single value
+    input: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Notation {
+    Json,
+    Text,
+}
+
+/// A subcommand ready to convert values
+struct Converter {
+    codec: Box<dyn Codec>,
+    schema: Value,
+    command: ConvertCommand,
+    pretty: bool,
+}
+
+impl Converter {
+    async fn new(command: ConvertCommand, backend: Backend, pretty: bool) -> Result<Converter> {
+        let schema = match &command {
+            ConvertCommand::Decode(conversion) | ConvertCommand::Encode { conversion, .. } => {
+                michelson(&std::fs::read_to_string(&conversion.schema)?)?
+            }
+            ConvertCommand::Convert { .. } => Value::Null,
+        };
+        // No conversion backend is needed to change the notation
+        let codec = match command {
+            ConvertCommand::Convert { .. } => Box::new(NativeCodec),
+            _ => codec(backend).await?,
+        };
+        Ok(Converter {
+            codec,
+            schema,
+            command,
+            pretty,
+        })
+    }
+
+    fn input(&self) -> Option<&Path> {
+        match &self.command {
+            ConvertCommand::Decode(conversion) | ConvertCommand::Encode { conversion, .. } => {
+                conversion.input.as_deref()
+            }
+            ConvertCommand::Convert { input, .. } => input.as_deref(),
+        }
+    }
+
+    /// Convert a single value
+    async fn convert(&self, input: &str) -> Result<String> {
+        match &self.command {
+            ConvertCommand::Decode(_) => {
+                let michelson = michelson(input)?;
+                let decoded = self.codec.decode(michelson, self.schema.clone()).await?;
+                self.json(&decoded)
+            }
+            ConvertCommand::Encode { text, .. } => {
+                let data = serde_json::from_str(input)?;
+                let encoded = self.codec.encode(data, self.schema.clone()).await?;
+                if *text {
+                    Ok(Micheline::from_json_value(encoded)?.to_michelson())
+                } else {
+                    self.json(&encoded)
+                }
+            }
+            ConvertCommand::Convert {
+                to: Notation::Text, ..
+            } => Ok(Micheline::from_str(input)?.to_michelson()),
+            ConvertCommand::Convert {
+                to: Notation::Json, ..
+            } => self.json(&parse_michelson(input)?.to_json_value()?),
+        }
+    }
+
+    fn json(&self, value: &Value) -> Result<String> {
+        let json = if self.pretty {
+            serde_json::to_string_pretty(value)?
+        } else {
+            serde_json::to_string(value)?
+        };
+        Ok(json)
+    }
+}
+
+/// Start the conversion backend
+async fn codec(backend: Backend) -> Result<Box<dyn Codec>> {
+    match backend {
+        Backend::Node => {
+            let bundle = install_parser_in(None).await?;
+            Ok(Box::new(ParserBuilder::new().bundle(bundle).build()?))
+        }
+        Backend::Native => Ok(Box::new(NativeCodec)),
+    }
+}
+
+/// A Michelson expression, in JSON or in the Michelson notation
+///
+/// The input is taken as JSON only if it's a Micheline expression:
+/// literals like `42` or `"foo"` are valid JSON, but are read in the Michelson notation.
+fn michelson(input: &str) -> Result<Value> {
+    if let Ok(json) = serde_json::from_str::<Value>(input) {
+        if Micheline::from_json_value(json.clone()).is_ok() {
+            return Ok(json);
+        }
+    }
+    Ok(parse_michelson(input)?.to_json_value()?)
+}
+
+fn parse_michelson(input: &str) -> Result<Micheline> {
+    Micheline::parse_michelson(input).map_err(|err| Error::EncodingError(err.to_string()))
+}
+
+/// Convert the input file, or each line of the standard input
+///
+/// Returns `false` if a conversion failed.
+async fn run(converter: &Converter) -> Result<bool> {
+    if let Some(path) = converter.input() {
+        let input = std::fs::read_to_string(path)?;
+        println!("{}", converter.convert(&input).await?);
+        return Ok(true);
+    }
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut success = true;
+    let mut number = 0;
+    while let Some(line) = lines.next_line().await? {
+        number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match converter.convert(&line).await {
+            Ok(output) => println!("{}", output),
+            Err(err) => {
+                eprintln!("line {}: {}", number, err);
+                success = false;
+            }
+        }
+    }
+    Ok(success)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Convert(command) => match Converter::new(command, cli.backend, cli.pretty).await {
+            Ok(converter) => run(&converter).await,
+            Err(err) => Err(err),
+        },
+        Command::Repl => match codec(cli.backend).await {
+            Ok(codec) => repl::Repl::new(codec).run().await.map(|()| true),
+            Err(err) => Err(err),
+        },
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
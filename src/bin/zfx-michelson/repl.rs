@@ -0,0 +1,258 @@
+//! The `repl` subcommand: convert values interactively with one parser process
+//!
+//! ```text
+//! > :schema pair (int %a) (address %owner)
+//! a: int
+//! owner: address
+//! > Pair 1 "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb"
+//! ```
+//!
+//! Michelson values (in JSON or in the Michelson notation) are decoded, other JSON values
+//! are encoded. Input is read until the brackets are balanced, so values can be pasted
+//! over several lines.
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use zfx_michelson::micheline::Micheline;
+use zfx_michelson::{Codec, Error, Result};
+
+use std::io::Write;
+
+const HELP: &str = "\
+:schema <type or file>  load a schema (in JSON or in the Michelson notation)
+:fields                 show the fields of the schema
+:decode <value>         decode a Michelson value
+:encode <value>         encode a Taquito-style JSON value
+:history                show the history
+!<n>                    run entry <n> of the history again
+:help                   show this message
+:quit                   exit
+
+Other input is decoded if it's a Michelson value, encoded otherwise.";
+
+pub struct Repl {
+    codec: Box<dyn Codec>,
+    schema: Option<Micheline>,
+    history: Vec<String>,
+}
+
+impl Repl {
+    pub fn new(codec: Box<dyn Codec>) -> Repl {
+        Repl {
+            codec,
+            schema: None,
+            history: vec![],
+        }
+    }
+
+    /// Read, evaluate and print until the end of the input or `:quit`
+    pub async fn run(&mut self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut input = String::new();
+        loop {
+            prompt(if input.is_empty() { "> " } else { ". " })?;
+            let line = match lines.next_line().await? {
+                Some(line) => line,
+                None => return Ok(()),
+            };
+            input.push_str(&line);
+            input.push('\n');
+            if !balanced(&input) {
+                continue;
+            }
+            let entry = std::mem::take(&mut input);
+            let entry = entry.trim();
+            if entry == ":quit" {
+                return Ok(());
+            }
+            if !entry.is_empty() {
+                println!("{}", self.eval(entry).await);
+            }
+        }
+    }
+
+    /// Evaluate an entry, returns the output to print
+    pub async fn eval(&mut self, entry: &str) -> String {
+        if let Some(number) = entry.strip_prefix('!') {
+            let previous = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|n| self.history.get(n));
+            return match previous {
+                Some(previous) => {
+                    let previous = previous.clone();
+                    format!("{}\n{}", previous, self.eval_entry(&previous).await)
+                }
+                None => format!("error: no history entry `{}`", number),
+            };
+        }
+        if entry == ":history" {
+            return self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(n, entry)| format!("{:>4}  {}", n + 1, entry))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        self.history.push(entry.to_owned());
+        self.eval_entry(entry).await
+    }
+
+    async fn eval_entry(&mut self, entry: &str) -> String {
+        let (command, argument) = match entry.split_once(char::is_whitespace) {
+            Some((command, argument)) if command.starts_with(':') => (command, argument.trim()),
+            _ if entry.starts_with(':') => (entry, ""),
+            _ => ("", entry),
+        };
+        let result = match command {
+            ":help" => Ok(HELP.to_owned()),
+            ":schema" => self.load_schema(argument),
+            ":fields" => self.schema().map(fields),
+            ":decode" => match michelson(argument) {
+                Some(michelson) => self.decode(michelson).await,
+                None => Err(Error::EncodingError(format!(
+                    "not a Michelson value: {}",
+                    argument
+                ))),
+            },
+            ":encode" => self.encode(argument).await,
+            "" => match michelson(argument) {
+                Some(michelson) => self.decode(michelson).await,
+                None => self.encode(argument).await,
+            },
+            _ => Err(Error::EncodingError(format!(
+                "unknown command `{}`, see `:help`",
+                command
+            ))),
+        };
+        result.unwrap_or_else(|err| describe(&err))
+    }
+
+    fn schema(&self) -> Result<&Micheline> {
+        self.schema
+            .as_ref()
+            .ok_or_else(|| Error::EncodingError("no schema, see `:schema`".to_owned()))
+    }
+
+    fn load_schema(&mut self, argument: &str) -> Result<String> {
+        let text = match std::fs::read_to_string(argument) {
+            Ok(text) => text,
+            Err(_) => argument.to_owned(),
+        };
+        let schema = match serde_json::from_str(&text) {
+            Ok(json) => Micheline::from_json_value(json)?,
+            Err(_) => Micheline::parse_michelson(&text)
+                .map_err(|err| Error::EncodingError(err.to_string()))?,
+        };
+        let output = fields(&schema);
+        self.schema = Some(schema);
+        Ok(output)
+    }
+
+    async fn decode(&self, michelson: Value) -> Result<String> {
+        let schema = self.schema()?.clone().to_json_value()?;
+        let decoded = self.codec.decode(michelson, schema).await?;
+        Ok(serde_json::to_string_pretty(&decoded)?)
+    }
+
+    async fn encode(&self, argument: &str) -> Result<String> {
+        let schema = self.schema()?.clone().to_json_value()?;
+        let data = serde_json::from_str(argument)?;
+        let encoded = self.codec.encode(data, schema).await?;
+        Ok(Micheline::from_json_value(encoded)?.to_michelson())
+    }
+}
+
+fn prompt(prompt: &str) -> Result<()> {
+    print!("{}", prompt);
+    std::io::stdout().flush()?;
+    Ok(())
+}
+
+/// A Michelson value in JSON or in the Michelson notation
+///
+/// Unlike the `decode` subcommand, valid JSON that isn't a Micheline expression (e.g. `42`
+/// or `null`) is not read in the Michelson notation: it's a Taquito-style value to encode.
+fn michelson(input: &str) -> Option<Value> {
+    match serde_json::from_str::<Value>(input) {
+        Ok(json) => Micheline::from_json_value(json.clone()).ok().map(|_| json),
+        Err(_) => Micheline::parse_michelson(input)
+            .ok()
+            .and_then(|michelson| michelson.to_json_value().ok()),
+    }
+}
+
+/// Whether the brackets of `input` are closed, ignoring strings
+fn balanced(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut string = false;
+    let mut escaped = false;
+    for c in input.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if string => escaped = true,
+            '"' => string = !string,
+            '{' | '[' | '(' if !string => depth += 1,
+            '}' | ']' | ')' if !string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0 && !string
+}
+
+/// The fields of a schema, from its field annotations, with their types
+fn fields(schema: &Micheline) -> String {
+    let mut out = vec![];
+    add_fields(schema, 0, &mut out);
+    if out.is_empty() {
+        out.push(format!("(no fields): {}", schema.to_michelson()));
+    }
+    out.join("\n")
+}
+
+fn add_fields(schema: &Micheline, depth: usize, out: &mut Vec<String>) {
+    let (prim, args, annots) = match schema {
+        Micheline::Prim { prim, args, annots } => (prim, args, annots),
+        _ => return,
+    };
+    let field = annots.iter().find_map(|annot| annot.strip_prefix('%'));
+    let nested = prim == "pair" || prim == "or";
+    let depth = match field {
+        Some(field) => {
+            let typ = if nested {
+                prim.clone()
+            } else {
+                let mut typ = schema.clone();
+                if let Micheline::Prim { annots, .. } = &mut typ {
+                    annots.retain(|annot| !annot.starts_with('%'));
+                }
+                typ.to_michelson()
+            };
+            out.push(format!("{}{}: {}", "  ".repeat(depth), field, typ));
+            depth + 1
+        }
+        None => depth,
+    };
+    if nested {
+        for arg in args {
+            add_fields(arg, depth, out);
+        }
+    }
+}
+
+/// An error message, with the path of the failing type for conversion errors
+fn describe(err: &Error) -> String {
+    match err {
+        Error::InvalidAddress(error) => format!("invalid address: {}", error),
+        Error::MapKeyMismatch(error) => format!("map key mismatch: {}", error),
+        Error::MissingField(error) => format!("missing field: {}", error),
+        Error::InvalidValue(error) => format!("invalid value: {}", error),
+        Error::EncodeError { error } | Error::DecodeError { error } => match error.as_str() {
+            Some(message) => format!("error: {}", message),
+            None => format!("error: {}", error),
+        },
+        Error::EncodingError(message) => format!("error: {}", message),
+        err => format!("error: {}", err),
+    }
+}
@@ -0,0 +1,216 @@
+//! Synchronous decoder/encoder ([`Parser`]), for callers without an async runtime
+//!
+//! Requires the `blocking` feature.
+//!
+//! ```no_run
+//! use serde_json::json;
+//! use zfx_michelson::blocking::{install_parser, Parser};
+//!
+//! install_parser().unwrap();
+//! let parser = Parser::new().unwrap();
+//! let decoded = parser.decode(json!({ "int": "-26" }), json!({ "prim": "int" }));
+//! ```
+use crate::michelson::{
+    MichelsonV1Expression, Request, RequestContent, Response, ResponseContent, SchemaHandle,
+};
+use crate::pack::encode_hex;
+use crate::{install, Error, ParserBuilder, Result};
+
+use serde_json::Value;
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout};
+use std::sync::Mutex;
+
+/// Install the necessary Javascript code in the current directory,
+/// see `crate::install_parser_in`
+pub fn install_parser() -> Result<PathBuf> {
+    install::install_parser_in(Some(Path::new(".")))
+}
+
+/// Install the necessary Javascript code in `dir`, or in a private temporary directory,
+/// see `crate::install_parser_in`
+pub fn install_parser_in(dir: Option<&Path>) -> Result<PathBuf> {
+    install::install_parser_in(dir)
+}
+
+/// The synchronous counterpart of `crate::Parser`, backed by a NodeJS process using Taquito
+///
+/// Requests are handled one at a time: concurrent callers wait for their turn.
+/// The [timeout](ParserBuilder::timeout) setting is not supported.
+/// The NodeJS process is killed when the `Parser` is dropped.
+pub struct Parser {
+    child: Child,
+    pipes: Mutex<Pipes>,
+}
+
+struct Pipes {
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: usize,
+}
+
+impl Parser {
+    /// Create a new `Parser` instance, **this starts a new NodeJS process**
+    ///
+    /// The default settings of [`ParserBuilder`] are used.
+    pub fn new() -> Result<Parser> {
+        Parser::with_builder(&ParserBuilder::new())
+    }
+
+    /// Start a NodeJS process with the settings of `builder`
+    pub fn with_builder(builder: &ParserBuilder) -> Result<Parser> {
+        let mut child = builder.command()?.spawn()?;
+        let stdin = child.stdin.take().expect("couldn't get stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("couldn't get stdout"));
+        Ok(Parser {
+            child,
+            pipes: Mutex::new(Pipes {
+                stdin,
+                stdout,
+                next_id: 0,
+            }),
+        })
+    }
+
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    pub fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let content = RequestContent::Encode { data, schema };
+        self.request(content)?.encoded()
+    }
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    pub fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        let content = RequestContent::Decode { michelson, schema };
+        self.request(content)?.decoded()
+    }
+
+    /// Encode several values with the same schema, in a single request,
+    /// see `crate::Parser::encode_batch`
+    pub fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        let content = RequestContent::EncodeBatch { data, schema };
+        self.request(content)?.encoded_batch()
+    }
+
+    /// Decode several values with the same schema, in a single request,
+    /// see `crate::Parser::decode_batch`
+    pub fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        let content = RequestContent::DecodeBatch { michelson, schema };
+        self.request(content)?.decoded_batch()
+    }
+
+    /// Register `schema` in the parser process, to convert values by its handle
+    ///
+    /// The schema is sent and parsed only once, instead of once per value.
+    /// The handle is valid until it's [unregistered](Parser::unregister_schema),
+    /// or the process exits.
+    pub fn register_schema(&self, schema: MichelsonV1Expression) -> Result<SchemaHandle> {
+        let content = RequestContent::RegisterSchema { schema };
+        self.request(content)?.registered()
+    }
+
+    /// Forget a registered schema, returns `false` if it wasn't registered
+    pub fn unregister_schema(&self, handle: SchemaHandle) -> Result<bool> {
+        let content = RequestContent::UnregisterSchema { handle };
+        self.request(content)?.unregistered()
+    }
+
+    /// Like [`encode`](Parser::encode), with a [registered](Parser::register_schema) schema
+    ///
+    /// Returns [`Error::UnknownSchema`] if the schema isn't registered.
+    pub fn encode_registered(
+        &self,
+        data: Value,
+        handle: SchemaHandle,
+    ) -> Result<MichelsonV1Expression> {
+        let content = RequestContent::EncodeRegistered { data, handle };
+        self.request(content)?.encoded()
+    }
+
+    /// Like [`decode`](Parser::decode), with a [registered](Parser::register_schema) schema
+    ///
+    /// Returns [`Error::UnknownSchema`] if the schema isn't registered.
+    pub fn decode_registered(
+        &self,
+        michelson: MichelsonV1Expression,
+        handle: SchemaHandle,
+    ) -> Result<Value> {
+        let content = RequestContent::DecodeRegistered { michelson, handle };
+        self.request(content)?.decoded()
+    }
+
+    /// Serialise `data`, a Michelson value of type `schema`, like the `PACK` instruction,
+    /// see `crate::Parser::pack`
+    pub fn pack(
+        &self,
+        data: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<u8>> {
+        let content = RequestContent::Pack { data, schema };
+        self.request(content)?.packed()
+    }
+
+    /// Deserialise packed `bytes` (with the `0x05` prefix) to a Michelson value of type `schema`,
+    /// like the `UNPACK` instruction
+    pub fn unpack(
+        &self,
+        bytes: &[u8],
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let bytes = encode_hex(bytes);
+        let content = RequestContent::Unpack { bytes, schema };
+        self.request(content)?.decoded()
+    }
+
+    /// Send a request and wait for its response
+    ///
+    /// Returns [`Error::ReadNone`] if the child process exited before responding.
+    fn request(&self, content: RequestContent) -> Result<ResponseContent> {
+        let mut pipes = self.pipes.lock().unwrap_or_else(|err| err.into_inner());
+        let id = pipes.next_id;
+        pipes.next_id += 1;
+        let mut line = serde_json::to_string(&Request { id, content })?;
+        line.push('\n');
+        pipes.stdin.write_all(line.as_bytes())?;
+        pipes.stdin.flush()?;
+        loop {
+            line.clear();
+            if pipes.stdout.read_line(&mut line)? == 0 {
+                return Err(Error::ReadNone);
+            }
+            match serde_json::from_str::<Response>(&line) {
+                Ok(response) if response.id == id => return Ok(response.content),
+                // Not a response, e.g. a stray log line, or the response of an abandoned request
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Drop for Parser {
+    /// Kill and reap the child process
+    fn drop(&mut self) {
+        // Fails if the process already exited
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,245 @@
+//! Backend-agnostic interface to the conversions ([`Codec`])
+//!
+//! Application code can be written against [`Codec`] (or `Box<dyn Codec>`)
+//! and the actual backend chosen at runtime with [`Backend`].
+use crate::micheline::Micheline;
+use crate::michelson::MichelsonV1Expression;
+#[cfg(feature = "async-tokio")]
+use crate::Parser;
+use crate::{native, Error, Result};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use std::str::FromStr;
+
+/// Encoding and decoding between Taquito-style JSON and Michelson
+///
+/// The arguments and results are the same as those of `Parser::encode` and `Parser::decode`.
+#[async_trait]
+pub trait Codec: Send + Sync {
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression>;
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value>;
+
+    /// Encode several values with the same schema, each value has its own result
+    ///
+    /// By default the values are encoded one by one.
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        let mut results = Vec::with_capacity(data.len());
+        for data in data {
+            results.push(self.encode(data, schema.clone()).await);
+        }
+        Ok(results)
+    }
+
+    /// Decode several values with the same schema, each value has its own result
+    ///
+    /// By default the values are decoded one by one.
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        let mut results = Vec::with_capacity(michelson.len());
+        for michelson in michelson {
+            results.push(self.decode(michelson, schema.clone()).await);
+        }
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl<C: Codec + ?Sized> Codec for Box<C> {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        (**self).encode(data, schema).await
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        (**self).decode(michelson, schema).await
+    }
+
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        (**self).encode_batch(data, schema).await
+    }
+
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        (**self).decode_batch(michelson, schema).await
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+#[async_trait]
+impl Codec for Parser {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        Parser::encode(self, data, schema).await
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        Parser::decode(self, michelson, schema).await
+    }
+
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        Parser::encode_batch(self, data, schema).await
+    }
+
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        Parser::decode_batch(self, michelson, schema).await
+    }
+}
+
+/// The [`native`] conversions as a [`Codec`], no child process is needed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeCodec;
+
+#[async_trait]
+impl Codec for NativeCodec {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let schema = Micheline::from_json_value(schema)?;
+        Ok(native::encode(&data, &schema)?.to_json_value()?)
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        let michelson = Micheline::from_json_value(michelson)?;
+        let schema = Micheline::from_json_value(schema)?;
+        native::decode(&michelson, &schema)
+    }
+}
+
+/// The available [`Codec`] implementations, e.g. to select one from a configuration file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// `Parser`, the NodeJS process using Taquito (requires the `async-tokio` feature)
+    #[cfg(feature = "async-tokio")]
+    Node,
+    /// [`NativeCodec`]
+    Native,
+}
+
+impl Backend {
+    /// Create a codec of this kind
+    ///
+    /// For `Backend::Node`, see `Parser::try_new` and `install_parser`:
+    /// fails if the NodeJS process cannot be started.
+    pub fn codec(&self) -> Result<Box<dyn Codec>> {
+        match self {
+            #[cfg(feature = "async-tokio")]
+            Backend::Node => Ok(Box::new(Parser::try_new()?)),
+            Backend::Native => Ok(Box::new(NativeCodec)),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Backend> {
+        match s {
+            #[cfg(feature = "async-tokio")]
+            "node" => Ok(Backend::Node),
+            "native" => Ok(Backend::Native),
+            _ => Err(Error::EncodingError(format!("unknown backend `{}`", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    async fn roundtrip<C: Codec>(codec: &C) -> Result<Value> {
+        let schema = json!({ "prim": "pair", "args": [
+            { "prim": "int", "annots": ["%a"] },
+            { "prim": "string", "annots": ["%b"] },
+        ] });
+        let data = json!({ "a": "1", "b": "foo" });
+        let encoded = codec.encode(data, schema.clone()).await?;
+        assert_eq!(
+            encoded,
+            json!({ "prim": "Pair", "args": [{ "int": "1" }, { "string": "foo" }] })
+        );
+        codec.decode(encoded, schema).await
+    }
+
+    #[tokio::test]
+    async fn test_native_codec() {
+        let codec = "native".parse::<Backend>().unwrap().codec().unwrap();
+        let decoded = roundtrip(&codec).await.unwrap();
+        assert_eq!(decoded, json!({ "a": "1", "b": "foo" }));
+    }
+
+    #[tokio::test]
+    async fn test_default_batch() {
+        let codec = NativeCodec;
+        let michelson = vec![json!({ "int": "1" }), json!({ "string": "a" })];
+        let results = codec
+            .decode_batch(michelson, json!({ "prim": "int" }))
+            .await
+            .unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &json!("1"));
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn test_backend_from_str() {
+        assert_eq!("native".parse::<Backend>().unwrap(), Backend::Native);
+        assert!("python".parse::<Backend>().is_err());
+    }
+}
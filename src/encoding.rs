@@ -0,0 +1,363 @@
+//! Tezos-specific encodings of Michelson values
+//!
+//! Values of domain-specific types (`address`, `key`, `signature`, ...) have a readable form,
+//! a Base58Check-encoded string with a type-specific prefix, and an optimised binary form.
+//! Timestamps are either RFC 3339 strings or seconds since the Unix epoch.
+//!
+//! The functions in this module convert between these representations,
+//! decoders return `None` if the input is not valid.
+use std::fmt::Write;
+
+pub const PREFIX_TZ1: &[u8] = &[6, 161, 159];
+pub const PREFIX_TZ2: &[u8] = &[6, 161, 161];
+pub const PREFIX_TZ3: &[u8] = &[6, 161, 164];
+pub const PREFIX_TZ4: &[u8] = &[6, 161, 166];
+pub const PREFIX_KT1: &[u8] = &[2, 90, 121];
+pub const PREFIX_TXR1: &[u8] = &[1, 128, 120, 31];
+pub const PREFIX_SR1: &[u8] = &[6, 124, 117];
+
+pub const PREFIX_EDPK: &[u8] = &[13, 15, 37, 217];
+pub const PREFIX_SPPK: &[u8] = &[3, 254, 226, 86];
+pub const PREFIX_P2PK: &[u8] = &[3, 178, 139, 127];
+pub const PREFIX_BLPK: &[u8] = &[6, 149, 135, 204];
+
+pub const PREFIX_EDSIG: &[u8] = &[9, 245, 205, 134, 18];
+pub const PREFIX_SPSIG: &[u8] = &[13, 115, 101, 19, 63];
+pub const PREFIX_P2SIG: &[u8] = &[54, 240, 44, 52];
+pub const PREFIX_SIG: &[u8] = &[4, 130, 43];
+pub const PREFIX_BLSIG: &[u8] = &[40, 171, 64, 207];
+
+pub const PREFIX_NET: &[u8] = &[87, 82, 0];
+pub const PREFIX_EXPR: &[u8] = &[13, 44, 64, 27];
+
+const HASH_LEN: usize = 20;
+
+/// Public key hashes: prefix and binary tag
+const KEY_HASHES: [(&[u8], u8); 4] = [
+    (PREFIX_TZ1, 0),
+    (PREFIX_TZ2, 1),
+    (PREFIX_TZ3, 2),
+    (PREFIX_TZ4, 3),
+];
+
+/// Contract hashes: prefix and binary tag in `address`es
+const CONTRACT_HASHES: [(&[u8], u8); 3] = [(PREFIX_KT1, 1), (PREFIX_TXR1, 2), (PREFIX_SR1, 3)];
+
+/// Public keys: prefix, binary tag and length
+const KEYS: [(&[u8], u8, usize); 4] = [
+    (PREFIX_EDPK, 0, 32),
+    (PREFIX_SPPK, 1, 33),
+    (PREFIX_P2PK, 2, 33),
+    (PREFIX_BLPK, 3, 48),
+];
+
+/// Encode a payload with its prefix in Base58Check
+pub fn b58check_encode(prefix: &[u8], payload: &[u8]) -> String {
+    let data = [prefix, payload].concat();
+    bs58::encode(data).with_check().into_string()
+}
+
+/// Decode a Base58Check string, checking its prefix and payload length
+pub fn b58check_decode(s: &str, prefix: &[u8], len: usize) -> Option<Vec<u8>> {
+    let data = bs58::decode(s).with_check(None).into_vec().ok()?;
+    let payload = data.strip_prefix(prefix)?;
+    (payload.len() == len).then(|| payload.to_vec())
+}
+
+/// Binary form of a `key_hash` (`tz1`, `tz2`, `tz3` or `tz4`)
+pub fn decode_key_hash(s: &str) -> Option<Vec<u8>> {
+    KEY_HASHES.iter().find_map(|(prefix, tag)| {
+        let hash = b58check_decode(s, prefix, HASH_LEN)?;
+        Some([&[*tag], hash.as_slice()].concat())
+    })
+}
+
+/// Readable form of a binary `key_hash`
+pub fn encode_key_hash(bytes: &[u8]) -> Option<String> {
+    let (tag, hash) = bytes.split_first()?;
+    let (prefix, _) = KEY_HASHES.iter().find(|(_, t)| t == tag)?;
+    (hash.len() == HASH_LEN).then(|| b58check_encode(prefix, hash))
+}
+
+/// Binary form of an `address`, including the optional `%entrypoint` suffix
+pub fn decode_address(s: &str) -> Option<Vec<u8>> {
+    let (address, entrypoint) = match s.split_once('%') {
+        Some((address, entrypoint)) => (address, entrypoint),
+        None => (s, ""),
+    };
+    let mut bytes = if let Some(key_hash) = decode_key_hash(address) {
+        [&[0u8], key_hash.as_slice()].concat()
+    } else {
+        CONTRACT_HASHES.iter().find_map(|(prefix, tag)| {
+            let hash = b58check_decode(address, prefix, HASH_LEN)?;
+            Some([&[*tag], hash.as_slice(), &[0u8]].concat())
+        })?
+    };
+    bytes.extend_from_slice(entrypoint.as_bytes());
+    Some(bytes)
+}
+
+/// Readable form of a binary `address`
+pub fn encode_address(bytes: &[u8]) -> Option<String> {
+    if bytes.len() < 22 {
+        return None;
+    }
+    let (address, entrypoint) = bytes.split_at(22);
+    let mut s = match address[0] {
+        0 => encode_key_hash(&address[1..])?,
+        tag => {
+            let (prefix, _) = CONTRACT_HASHES.iter().find(|(_, t)| *t == tag)?;
+            if address[21] != 0 {
+                return None;
+            }
+            b58check_encode(prefix, &address[1..21])
+        }
+    };
+    if !entrypoint.is_empty() {
+        s.push('%');
+        s.push_str(std::str::from_utf8(entrypoint).ok()?);
+    }
+    Some(s)
+}
+
+/// Binary form of a public `key`
+pub fn decode_key(s: &str) -> Option<Vec<u8>> {
+    KEYS.iter().find_map(|(prefix, tag, len)| {
+        let key = b58check_decode(s, prefix, *len)?;
+        Some([&[*tag], key.as_slice()].concat())
+    })
+}
+
+/// Readable form of a binary public `key`
+pub fn encode_key(bytes: &[u8]) -> Option<String> {
+    let (tag, key) = bytes.split_first()?;
+    let (prefix, _, len) = KEYS.iter().find(|(_, t, _)| t == tag)?;
+    (key.len() == *len).then(|| b58check_encode(prefix, key))
+}
+
+/// Binary form of a `signature`
+pub fn decode_signature(s: &str) -> Option<Vec<u8>> {
+    [PREFIX_EDSIG, PREFIX_SPSIG, PREFIX_P2SIG, PREFIX_SIG]
+        .iter()
+        .find_map(|prefix| b58check_decode(s, prefix, 64))
+        .or_else(|| b58check_decode(s, PREFIX_BLSIG, 96))
+}
+
+/// Readable form of a binary `signature`, using the generic `sig` prefix
+///
+/// The binary form doesn't record the curve, except for BLS signatures which are longer.
+pub fn encode_signature(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        64 => Some(b58check_encode(PREFIX_SIG, bytes)),
+        96 => Some(b58check_encode(PREFIX_BLSIG, bytes)),
+        _ => None,
+    }
+}
+
+/// Binary form of a `chain_id`
+pub fn decode_chain_id(s: &str) -> Option<Vec<u8>> {
+    b58check_decode(s, PREFIX_NET, 4)
+}
+
+/// Readable form of a binary `chain_id`
+pub fn encode_chain_id(bytes: &[u8]) -> Option<String> {
+    (bytes.len() == 4).then(|| b58check_encode(PREFIX_NET, bytes))
+}
+
+/// Format seconds since the epoch like Javascript's `Date.toISOString`, e.g. `2022-08-01T12:00:00.000Z`
+pub fn format_timestamp(seconds: i64) -> String {
+    format_timestamp_parts(seconds, 0)
+}
+
+/// Format milliseconds since the epoch like Javascript's `Date.toISOString`,
+/// e.g. `2022-08-01T12:00:00.500Z`
+pub fn format_timestamp_millis(millis: i64) -> String {
+    format_timestamp_parts(millis.div_euclid(1000), millis.rem_euclid(1000))
+}
+
+fn format_timestamp_parts(seconds: i64, millis: i64) -> String {
+    let days = seconds.div_euclid(86400);
+    let secs = seconds.rem_euclid(86400);
+    let (y, m, d) = civil_from_days(days);
+    let mut s = String::new();
+    if (0..=9999).contains(&y) {
+        let _ = write!(s, "{:04}", y);
+    } else {
+        let _ = write!(s, "{:+07}", y);
+    }
+    let _ = write!(
+        s,
+        "-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        m,
+        d,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        millis
+    );
+    s
+}
+
+/// Parse an RFC 3339 timestamp (e.g. `2022-08-01T12:00:00Z` or `2022-08-01T14:00:00.5+02:00`)
+/// to seconds since the epoch, fractional seconds are truncated
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    parse_timestamp_millis(s).map(|millis| millis.div_euclid(1000))
+}
+
+/// Parse an RFC 3339 timestamp to milliseconds since the epoch,
+/// digits after the milliseconds are truncated like Javascript's `Date` does
+pub fn parse_timestamp_millis(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    let num = |from: usize, len: usize| -> Option<i64> {
+        let digits = s.get(from..from + len)?;
+        if !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || !matches!(b[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    if b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (num(0, 4)?, num(5, 2)?, num(8, 2)?);
+    let (hour, minute, second) = (num(11, 2)?, num(14, 2)?, num(17, 2)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let mut rest = &s[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(|c| c.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        millis = format!("{:0<3}", &fraction[..len.min(3)]).parse().ok()?;
+        rest = &fraction[len..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = &rest[1..];
+            if offset.len() != 5 || offset.as_bytes()[2] != b':' {
+                return None;
+            }
+            let (h, m): (i64, i64) = (offset[..2].parse().ok()?, offset[3..].parse().ok()?);
+            sign * (h * 3600 + m * 60)
+        }
+    };
+    let days = days_from_civil(year, month, day);
+    Some((days * 86400 + hour * 3600 + minute * 60 + second - offset) * 1000 + millis)
+}
+
+// Conversion between days since the epoch and the proleptic Gregorian calendar,
+// see http://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pack::{decode_hex, encode_hex};
+
+    #[test]
+    fn test_addresses() {
+        let tz1 = "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c";
+        let bytes = decode_address(tz1).unwrap();
+        assert_eq!(bytes.len(), 22);
+        assert_eq!(&bytes[..2], &[0, 0]);
+        assert_eq!(encode_address(&bytes).unwrap(), tz1);
+
+        let kt1 = "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi%transfer";
+        let bytes = decode_address(kt1).unwrap();
+        assert_eq!(bytes[0], 1);
+        assert_eq!(bytes[21], 0);
+        assert_eq!(&bytes[22..], b"transfer");
+        assert_eq!(encode_address(&bytes).unwrap(), kt1);
+
+        assert_eq!(
+            encode_hex(&decode_key_hash("tz1Ke2h7sDdakHJQh8WX4Z372du1KChsksyU").unwrap()),
+            "000000000000000000000000000000000000000000"
+        );
+        assert!(decode_address("tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8d").is_none());
+        assert!(decode_address("foo").is_none());
+        assert!(encode_address(&[0; 21]).is_none());
+    }
+
+    #[test]
+    fn test_keys() {
+        let edpk = "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg";
+        let bytes = decode_key(edpk).unwrap();
+        assert_eq!(bytes.len(), 33);
+        assert_eq!(encode_key(&bytes).unwrap(), edpk);
+        assert!(decode_key("tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c").is_none());
+    }
+
+    #[test]
+    fn test_chain_id() {
+        let bytes = decode_hex("7a06a770").unwrap();
+        let chain_id = encode_chain_id(&bytes).unwrap();
+        assert_eq!(chain_id, "NetXdQprcVkpaWU");
+        assert_eq!(decode_chain_id(&chain_id).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_timestamp(1659355200), "2022-08-01T12:00:00.000Z");
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59.000Z");
+        assert_eq!(parse_timestamp("2022-08-01T12:00:00Z"), Some(1659355200));
+        assert_eq!(
+            parse_timestamp("2022-08-01T14:00:00.123+02:00"),
+            Some(1659355200)
+        );
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00.000Z"), Some(0));
+        assert_eq!(parse_timestamp("2022-13-01T12:00:00Z"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(
+            format_timestamp_millis(1659355200500),
+            "2022-08-01T12:00:00.500Z"
+        );
+        assert_eq!(format_timestamp_millis(-500), "1969-12-31T23:59:59.500Z");
+        assert_eq!(
+            parse_timestamp_millis("2022-08-01T14:00:00.1239+02:00"),
+            Some(1659355200123)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2022-08-01T12:00:00.5Z"),
+            Some(1659355200500)
+        );
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59.5Z"), Some(-1));
+    }
+}
@@ -0,0 +1,433 @@
+//! Contract call parameters: entrypoints of a `parameter` type
+//!
+//! A transaction calls a contract with a [`Parameters`] object, the name of an entrypoint and
+//! its argument. An entrypoint is a branch of the `or` tree of the contract's `parameter` type,
+//! named by its field annotation. It can be called directly by name, or through `%default`
+//! with its argument wrapped in `Left`/`Right` to reach it from the root (see [`Addressing`]).
+//!
+//! [`entrypoints`] lists the entrypoints of a contract, [`encode`] builds the parameters
+//! of a call and [`decode`] finds the entrypoint actually called.
+use crate::codec::Codec;
+use crate::micheline::Micheline;
+use crate::michelson::MichelsonV1Expression;
+use crate::{Error, Result};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The parameters of a contract call, as in the `parameters` of a transaction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameters {
+    pub entrypoint: String,
+    pub value: MichelsonV1Expression,
+}
+
+/// A contract call resolved to its entrypoint, with decoded arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub entrypoint: String,
+    /// The arguments in the format of `Parser::decode`
+    pub args: Value,
+}
+
+/// How a contract call addresses its entrypoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Addressing {
+    /// Call the entrypoint by its name, the value is its argument
+    Direct,
+    /// Call `%default`, the argument is wrapped in `Left`/`Right` to reach the entrypoint
+    Default,
+}
+
+/// A step from an `or` type to one of its branches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    Left,
+    Right,
+}
+
+/// An entrypoint of a contract
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entrypoint {
+    pub name: String,
+    /// The branches leading to the entrypoint from the root of the `parameter` type
+    pub path: Vec<Branch>,
+    /// The type of the argument, with its field annotation
+    pub arg_type: Micheline,
+}
+
+/// The entrypoints of a contract, from its `parameter` type (with or without the keyword)
+///
+/// Every branch of the `or` tree with a field annotation is an entrypoint, including the root
+/// (e.g. `parameter (or %root ...)`). If no branch is annotated `%default`, the root is
+/// the implicit `default` entrypoint, listed first. The entrypoints are listed depth-first,
+/// from the left. Returns [`Error::DuplicateEntrypoint`] if two branches have the same name.
+pub fn entrypoints(parameter: &Micheline) -> Result<Vec<Entrypoint>> {
+    let parameter = parameter_type(parameter);
+    let mut table = vec![];
+    collect(parameter, &mut vec![], &mut table);
+    for (i, entrypoint) in table.iter().enumerate() {
+        if table[..i].iter().any(|other| other.name == entrypoint.name) {
+            return Err(Error::DuplicateEntrypoint(entrypoint.name.clone()));
+        }
+    }
+    if !table.iter().any(|entrypoint| entrypoint.name == "default") {
+        let default = Entrypoint {
+            name: "default".to_owned(),
+            path: vec![],
+            arg_type: parameter.clone(),
+        };
+        table.insert(0, default);
+    }
+    Ok(table)
+}
+
+fn collect(typ: &Micheline, path: &mut Vec<Branch>, table: &mut Vec<Entrypoint>) {
+    if let Some(name) = entrypoint_name(typ) {
+        table.push(Entrypoint {
+            name: name.to_owned(),
+            path: path.clone(),
+            arg_type: typ.clone(),
+        });
+    }
+    if let Micheline::Prim { prim, args, .. } = typ {
+        if prim == "or" && args.len() == 2 {
+            for (branch, arg) in [(Branch::Left, &args[0]), (Branch::Right, &args[1])] {
+                path.push(branch);
+                collect(arg, path, table);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// The parameters calling `entrypoint` of a contract with the Taquito-style `data`
+///
+/// `parameter` is the contract's parameter type, see [`entrypoints`].
+/// The argument is encoded by `codec` with the type of the entrypoint.
+/// Returns [`Error::UnknownEntrypoint`] if the contract has no such entrypoint.
+pub async fn encode<C: Codec + ?Sized>(
+    codec: &C,
+    parameter: &Micheline,
+    entrypoint: &str,
+    data: Value,
+    addressing: Addressing,
+) -> Result<Parameters> {
+    let table = entrypoints(parameter)?;
+    let target = lookup(&table, entrypoint)?;
+    let value = codec
+        .encode(data, target.arg_type.clone().to_json_value()?)
+        .await?;
+    match addressing {
+        Addressing::Direct => Ok(Parameters {
+            entrypoint: entrypoint.to_owned(),
+            value,
+        }),
+        Addressing::Default => {
+            // An explicit `%default` hides the root
+            let default = lookup(&table, "default")?;
+            let path = target
+                .path
+                .strip_prefix(default.path.as_slice())
+                .ok_or_else(|| Error::EncodeError {
+                    error: json!(format!(
+                        "entrypoint `{}` is not reachable from `default`",
+                        entrypoint
+                    )),
+                })?;
+            Ok(Parameters {
+                entrypoint: "default".to_owned(),
+                value: wrap(path, value),
+            })
+        }
+    }
+}
+
+/// The entrypoint called by `parameters` and its arguments, decoded by `codec`
+///
+/// From the entrypoint named in `parameters` (e.g. `default`), the `or` tree is walked down
+/// according to the `Left`/`Right` wrapping of the value, until an entrypoint is reached.
+/// If there's none, the call is to the named entrypoint.
+pub async fn decode<C: Codec + ?Sized>(
+    codec: &C,
+    parameter: &Micheline,
+    parameters: &Parameters,
+) -> Result<Call> {
+    let table = entrypoints(parameter)?;
+    let start = lookup(&table, &parameters.entrypoint)?;
+    let value = Micheline::from_json_value(parameters.value.clone())?;
+    let (entrypoint, value) = resolve(&table, start, &value);
+    let args = codec
+        .decode(
+            value.clone().to_json_value()?,
+            entrypoint.arg_type.clone().to_json_value()?,
+        )
+        .await?;
+    Ok(Call {
+        entrypoint: entrypoint.name.clone(),
+        args,
+    })
+}
+
+/// The first entrypoint below `start` selected by the `Left`/`Right` wrapping of `value`,
+/// with its argument, or `start` and `value` if there's none
+fn resolve<'t, 'v>(
+    table: &'t [Entrypoint],
+    start: &'t Entrypoint,
+    value: &'v Micheline,
+) -> (&'t Entrypoint, &'v Micheline) {
+    let (mut typ, mut arg) = (&start.arg_type, value);
+    let mut path = start.path.clone();
+    loop {
+        let branches = match typ {
+            Micheline::Prim { prim, args, .. } if prim == "or" && args.len() == 2 => args,
+            _ => return (start, value),
+        };
+        let (branch, next) = match arg {
+            Micheline::Prim { prim, args, .. } if args.len() == 1 => match prim.as_str() {
+                "Left" => (Branch::Left, &args[0]),
+                "Right" => (Branch::Right, &args[0]),
+                _ => return (start, value),
+            },
+            _ => return (start, value),
+        };
+        typ = match branch {
+            Branch::Left => &branches[0],
+            Branch::Right => &branches[1],
+        };
+        arg = next;
+        path.push(branch);
+        if let Some(entrypoint) = table.iter().find(|entrypoint| entrypoint.path == path) {
+            return (entrypoint, arg);
+        }
+    }
+}
+
+/// The name of an entrypoint, its field annotation
+fn entrypoint_name(typ: &Micheline) -> Option<&str> {
+    match typ {
+        Micheline::Prim { annots, .. } => annots
+            .iter()
+            .find_map(|annot| annot.strip_prefix('%'))
+            .filter(|name| !name.is_empty()),
+        _ => None,
+    }
+}
+
+/// The type itself, without the `parameter` keyword
+fn parameter_type(parameter: &Micheline) -> &Micheline {
+    match parameter {
+        Micheline::Prim { prim, args, .. } if prim == "parameter" && args.len() == 1 => &args[0],
+        _ => parameter,
+    }
+}
+
+fn lookup<'t>(table: &'t [Entrypoint], entrypoint: &str) -> Result<&'t Entrypoint> {
+    table
+        .iter()
+        .find(|e| e.name == entrypoint)
+        .ok_or_else(|| Error::UnknownEntrypoint(entrypoint.to_owned()))
+}
+
+/// Wrap `value` in `Left`/`Right`, the first branch of `path` is the outermost
+fn wrap(path: &[Branch], value: MichelsonV1Expression) -> MichelsonV1Expression {
+    path.iter().rev().fold(value, |value, branch| {
+        let prim = match branch {
+            Branch::Left => "Left",
+            Branch::Right => "Right",
+        };
+        json!({ "prim": prim, "args": [value] })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NativeCodec;
+
+    use futures_lite::future::block_on;
+
+    const COUNTER: &str = r#"
+        { "prim": "or",
+          "args":
+            [ { "prim": "or",
+                "args":
+                  [ { "prim": "int", "annots": [ "%decrement" ] },
+                    { "prim": "int", "annots": [ "%increment" ] } ] },
+              { "prim": "unit", "annots": [ "%reset" ] } ] }"#;
+
+    fn call(
+        parameter: &str,
+        entrypoint: &str,
+        data: Value,
+        addressing: Addressing,
+    ) -> Result<Parameters> {
+        let parameter = Micheline::from_str(parameter).unwrap();
+        block_on(encode(
+            &NativeCodec,
+            &parameter,
+            entrypoint,
+            data,
+            addressing,
+        ))
+    }
+
+    #[test]
+    fn test_encode_parameters() {
+        let direct = call(COUNTER, "increment", json!("1"), Addressing::Direct).unwrap();
+        assert_eq!(direct.entrypoint, "increment");
+        assert_eq!(direct.value, json!({ "int": "1" }));
+
+        let default = call(COUNTER, "increment", json!("1"), Addressing::Default).unwrap();
+        assert_eq!(default.entrypoint, "default");
+        assert_eq!(
+            default.value,
+            json!({ "prim": "Left", "args": [{ "prim": "Right", "args": [{ "int": "1" }] }] })
+        );
+
+        let reset = call(
+            COUNTER,
+            "reset",
+            json!({ "__unit__": null }),
+            Addressing::Default,
+        );
+        assert_eq!(
+            reset.unwrap().value,
+            json!({ "prim": "Right", "args": [{ "prim": "Unit" }] })
+        );
+
+        let root = call(
+            COUNTER,
+            "default",
+            json!({ "reset": { "__unit__": null } }),
+            Addressing::Direct,
+        );
+        assert_eq!(
+            root.unwrap().value,
+            json!({ "prim": "Right", "args": [{ "prim": "Unit" }] })
+        );
+
+        let response = call(COUNTER, "double", json!("1"), Addressing::Direct);
+        assert!(matches!(response, Err(Error::UnknownEntrypoint(name)) if name == "double"));
+    }
+
+    fn table(parameter: &str) -> Result<Vec<(String, Vec<Branch>)>> {
+        let parameter = Micheline::from_str(parameter).unwrap();
+        let table = entrypoints(&parameter)?;
+        Ok(table.into_iter().map(|e| (e.name, e.path)).collect())
+    }
+
+    #[test]
+    fn test_entrypoints() {
+        use Branch::{Left, Right};
+
+        let parameter = Micheline::from_str(COUNTER).unwrap();
+        let counter = entrypoints(&parameter).unwrap();
+        let names: Vec<_> = counter.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["default", "decrement", "increment", "reset"]);
+        assert_eq!(counter[0].path, []);
+        assert_eq!(counter[0].arg_type, parameter);
+        assert_eq!(counter[2].path, [Left, Right]);
+        assert_eq!(
+            counter[3].arg_type,
+            Micheline::from_str(r#"{ "prim": "unit", "annots": [ "%reset" ] }"#).unwrap()
+        );
+
+        // Root annotation, the root is also the implicit default
+        let root = r#"
+            { "prim": "or", "annots": [ "%root" ],
+              "args": [ { "prim": "nat", "annots": [ "%a" ] }, { "prim": "nat", "annots": [ "%" ] } ] }"#;
+        assert_eq!(
+            table(root).unwrap(),
+            [
+                ("default".to_owned(), vec![]),
+                ("root".to_owned(), vec![]),
+                ("a".to_owned(), vec![Left]),
+            ]
+        );
+
+        // Explicit default
+        let explicit = r#"
+            { "prim": "or",
+              "args": [ { "prim": "nat", "annots": [ "%a" ] }, { "prim": "nat", "annots": [ "%default" ] } ] }"#;
+        assert_eq!(
+            table(explicit).unwrap(),
+            [
+                ("a".to_owned(), vec![Left]),
+                ("default".to_owned(), vec![Right])
+            ]
+        );
+
+        let duplicate = r#"
+            { "prim": "or",
+              "args": [ { "prim": "nat", "annots": [ "%a" ] }, { "prim": "int", "annots": [ "%a" ] } ] }"#;
+        assert!(matches!(table(duplicate), Err(Error::DuplicateEntrypoint(name)) if name == "a"));
+    }
+
+    fn decode_call(parameter: &str, entrypoint: &str, value: Value) -> Result<Call> {
+        let parameter = Micheline::from_str(parameter).unwrap();
+        let parameters = Parameters {
+            entrypoint: entrypoint.to_owned(),
+            value,
+        };
+        block_on(decode(&NativeCodec, &parameter, &parameters))
+    }
+
+    #[test]
+    fn test_decode_parameters() {
+        let value =
+            json!({ "prim": "Left", "args": [{ "prim": "Left", "args": [{ "int": "1" }] }] });
+        let call = decode_call(COUNTER, "default", value).unwrap();
+        assert_eq!(call.entrypoint, "decrement");
+        assert_eq!(call.args, json!("1"));
+
+        let call = decode_call(COUNTER, "increment", json!({ "int": "2" })).unwrap();
+        assert_eq!(call.entrypoint, "increment");
+        assert_eq!(call.args, json!("2"));
+
+        let value = json!({ "prim": "Right", "args": [{ "prim": "Unit" }] });
+        let call = decode_call(COUNTER, "default", value).unwrap();
+        assert_eq!(call.entrypoint, "reset");
+        assert_eq!(call.args, json!({ "__unit__": null }));
+
+        let response = decode_call(COUNTER, "double", json!({ "int": "2" }));
+        assert!(matches!(response, Err(Error::UnknownEntrypoint(_))));
+    }
+
+    #[test]
+    fn test_decode_unnamed_branches() {
+        let parameter = r#"
+            { "prim": "or",
+              "args": [ { "prim": "nat", "annots": [ "%a" ] }, { "prim": "nat" } ] }"#;
+        let value = json!({ "prim": "Right", "args": [{ "int": "3" }] });
+        let call = decode_call(parameter, "default", value).unwrap();
+        assert_eq!(call.entrypoint, "default");
+        assert_eq!(call.args, json!({ "1": "3" }));
+    }
+
+    #[test]
+    fn test_encode_explicit_default() {
+        let parameter = r#"
+            { "prim": "parameter",
+              "args":
+                [ { "prim": "or",
+                    "args":
+                      [ { "prim": "or",
+                          "args":
+                            [ { "prim": "nat", "annots": [ "%a" ] },
+                              { "prim": "nat", "annots": [ "%b" ] } ],
+                          "annots": [ "%default" ] },
+                        { "prim": "nat", "annots": [ "%c" ] } ] } ] }"#;
+        let b = call(parameter, "b", json!("2"), Addressing::Default).unwrap();
+        assert_eq!(b.entrypoint, "default");
+        assert_eq!(
+            b.value,
+            json!({ "prim": "Right", "args": [{ "int": "2" }] })
+        );
+
+        let response = call(parameter, "c", json!("2"), Addressing::Default);
+        assert!(matches!(response, Err(Error::EncodeError { .. })));
+        let c = call(parameter, "c", json!("2"), Addressing::Direct).unwrap();
+        assert_eq!(c.value, json!({ "int": "2" }));
+    }
+}
@@ -0,0 +1,251 @@
+//! Installation of the Javascript bundle run by the parser processes
+use crate::michelson::BUNDLE_NAME;
+use crate::{Error, Result};
+
+use include_dir::{include_dir, Dir};
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCRIPTS_DIR: Dir<'_> = include_dir!("./scripts");
+
+const BUNDLE_HEADER: &str = "// zfx-michelson bundle ";
+
+/// See [`install_parser_in`](crate::install_parser_in)
+pub(crate) fn install_parser_in(dir: Option<&Path>) -> Result<PathBuf> {
+    let bundle = SCRIPTS_DIR.get_file(BUNDLE_NAME).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "the parser bundle was not included in the build",
+        )
+    })?;
+    install(bundle.contents(), dir)
+}
+
+/// 64-bit FNV-1a, to detect outdated or corrupted bundles
+fn bundle_hash(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn install(contents: &[u8], dir: Option<&Path>) -> Result<PathBuf> {
+    let hash = bundle_hash(contents);
+    let dir = match dir {
+        Some(dir) => dir.to_owned(),
+        None => private_temp_dir(&hash)?,
+    };
+    let path = dir.join(BUNDLE_NAME);
+    let header = format!("{}{}\n", BUNDLE_HEADER, hash);
+    if let Ok(installed) = fs::read(&path) {
+        if installed.starts_with(header.as_bytes())
+            && bundle_hash(&installed[header.len()..]) == hash
+        {
+            return Ok(path);
+        }
+    }
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let temp = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        BUNDLE_NAME,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let written = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(header.as_bytes())?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)
+    })();
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    Ok(path)
+}
+
+/// A directory in the system's temporary directory, only accessible by the current user
+///
+/// The directory is named after the user and the bundle, so that their processes install it once.
+/// If it was created by someone else, a new directory with a random name is used instead.
+fn private_temp_dir(hash: &str) -> Result<PathBuf> {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    let name = format!("zfx-michelson-{}-{}", user, hash);
+    let dir = std::env::temp_dir().join(&name);
+    let mut builder = private_dir_builder();
+    builder.recursive(true);
+    builder.create(&dir)?;
+    if is_private(&dir)? {
+        return Ok(dir);
+    }
+    for _ in 0..16 {
+        let dir = std::env::temp_dir().join(format!("{}-{:016x}", name, random()));
+        // Not recursive: fails if the directory exists
+        match private_dir_builder().create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(Error::IoError(std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        format!("{} is not a private directory", dir.display()),
+    )))
+}
+
+fn private_dir_builder() -> fs::DirBuilder {
+    #[allow(unused_mut)]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+}
+
+/// Whether `dir` is a directory owned by the current user, and only accessible by them
+fn is_private(dir: &Path) -> Result<bool> {
+    let metadata = fs::symlink_metadata(dir)?;
+    #[cfg(unix)]
+    let private = {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        // SAFETY: `geteuid` is always successful
+        let euid = unsafe { libc::geteuid() };
+        metadata.uid() == euid && metadata.permissions().mode() & 0o077 == 0
+    };
+    #[cfg(not(unix))]
+    let private = true;
+    Ok(metadata.is_dir() && private)
+}
+
+/// A random number, from the randomly seeded hasher of the standard library
+fn random() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A directory removed with its contents when dropped, at the end of the test
+    struct TestDir(PathBuf);
+
+    impl std::ops::Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TestDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn test_dir(name: &str) -> TestDir {
+        let dir = std::env::temp_dir().join(format!(
+            "zfx-michelson-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+
+    #[test]
+    fn test_install() {
+        let dir = test_dir("install");
+        let path = install(b"bundle", Some(&dir)).unwrap();
+        assert_eq!(path, dir.join(BUNDLE_NAME));
+        let installed = fs::read_to_string(&path).unwrap();
+        assert!(installed.starts_with(BUNDLE_HEADER));
+        assert!(installed.ends_with("\nbundle"));
+
+        // Up to date, not rewritten
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        install(b"bundle", Some(&dir)).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        // Corrupted or outdated
+        fs::write(&path, installed.replace("bundle", "bungle")).unwrap();
+        install(b"bundle", Some(&dir)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), installed);
+        install(b"bundle v2", Some(&dir)).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().ends_with("\nbundle v2"));
+    }
+
+    #[test]
+    fn test_install_concurrently() {
+        let dir = test_dir("concurrent");
+        let installs: Vec<_> = (0..8)
+            .map(|_| {
+                let dir = dir.to_owned();
+                std::thread::spawn(move || install(b"bundle", Some(&dir)).unwrap())
+            })
+            .collect();
+        for handle in installs {
+            handle.join().unwrap();
+        }
+        let files = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, 1);
+    }
+
+    #[test]
+    fn test_install_errors() {
+        let dir = test_dir("errors");
+        assert!(matches!(
+            install(b"bundle", Some(&dir.join("missing"))),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_temp_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = install(b"private bundle", None).unwrap();
+        let dir = TestDir(path.parent().unwrap().to_owned());
+        assert!(dir.starts_with(std::env::temp_dir()));
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("private");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(is_private(&dir).unwrap());
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(!is_private(&dir).unwrap());
+    }
+
+    #[test]
+    fn test_random() {
+        assert_ne!(random(), random());
+    }
+}
@@ -22,18 +22,97 @@ const { Schema, UnitValue } = require("@taquito/michelson-encoder");
 const { BigNumber } = require("bignumber.js");
 const util = require("util");
 
-exports.jsonDecode = (schema, input) => {
-  const michelson = clone(input);
+exports.jsonDecode = (schema, input) => exports.jsonDecoder(schema)(input);
+
+exports.jsonEncode = (schema, input) => exports.jsonEncoder(schema)(input);
+
+// A decoding function for `schema`, to convert several values with the same `Schema`
+exports.jsonDecoder = (schema) => {
   const taquito_schema = new Schema(schema);
-  const raw_value = taquito_schema.Execute(michelson);
-  return postprocess(raw_value);
+  return (input) => {
+    const michelson = clone(input);
+    const raw_value = taquito_schema.Execute(michelson);
+    return postprocess(raw_value);
+  };
 };
 
-exports.jsonEncode = (schema, input) => {
-  const data = clone(input);
+// An encoding function for `schema`, to convert several values with the same `Schema`
+exports.jsonEncoder = (schema) => {
   const taquito_schema = new Schema(schema);
-  const preprocessed_data = preprocessEncode(data);
-  return taquito_schema.Encode(preprocessed_data);
+  return (input) => {
+    const data = clone(input);
+    const preprocessed_data = preprocessEncode(data);
+    return taquito_schema.Encode(preprocessed_data);
+  };
+};
+
+/* Structured description of a conversion error thrown by Taquito
+
+  { class, message, path, field, value }
+
+  `path` locates the failing type in `schema`: field names, `key`/`value` for the
+  arguments of maps, and argument indices otherwise. It is `null` if the type isn't found.
+  `value` is the offending (sub-)value, it's missing if the value itself is missing.
+*/
+exports.jsonError = (schema, err) => {
+  if (err === null || typeof err !== "object") {
+    return { class: "Error", message: util.inspect(err), path: null, field: null };
+  }
+  const error = {
+    class: err.name || (err.constructor && err.constructor.name) || "Error",
+    message: err.message !== undefined ? String(err.message) : util.inspect(err),
+    path: null,
+    field: null,
+  };
+  // `MapTypecheckError` refers to the type of the map instead of a token
+  const node = err.token ? err.token.val : err.type;
+  let path = node ? findPath(schema, node) : null;
+  if (path && err.name === "MapTypecheckError") {
+    path = [...path, err.message.startsWith("key") ? "key" : "value"];
+  }
+  error.path = path;
+  error.field = node ? fieldName(node) : null;
+  if (err.value !== undefined) {
+    error.value = errorValue(err.value);
+  }
+  return error;
+};
+
+// The path of `target` in the `schema` tree, compared by identity
+const findPath = (schema, target) => {
+  if (schema === target) {
+    return [];
+  }
+  if (schema === null || typeof schema !== "object" || !Array.isArray(schema.args)) {
+    return null;
+  }
+  const isMap = schema.prim === "map" || schema.prim === "big_map";
+  for (let i = 0; i < schema.args.length; i++) {
+    const path = findPath(schema.args[i], target);
+    if (path !== null) {
+      const segment = isMap
+        ? ["key", "value"][i]
+        : fieldName(schema.args[i]) || String(i);
+      return [segment, ...path];
+    }
+  }
+  return null;
+};
+
+// The field annotation of a type, without `%`
+const fieldName = (node) => {
+  const annots = (node && node.annots) || [];
+  const field = annots.find((annot) => annot.startsWith("%"));
+  return field ? field.slice(1) : null;
+};
+
+// A JSON representation of a value found in a Taquito error
+const errorValue = (value) => {
+  try {
+    return clone(postprocess(value));
+  } catch (_) {
+    return util.inspect(value);
+  }
 };
 
 const preprocessEncode = (data) =>
@@ -1,20 +1,52 @@
 #![doc = include_str!("../README.md")]
 #![doc(html_logo_url = "https://avatars.githubusercontent.com/zfxlabs")]
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod codec;
+pub mod encoding;
+pub mod entrypoints;
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+mod install;
 pub mod micheline;
 pub mod michelson;
 pub mod michelson_map;
 pub mod michelson_types;
+pub mod native;
+#[cfg(feature = "async-tokio")]
+mod node;
+pub mod pack;
+#[cfg(feature = "async-tokio")]
+pub mod pool;
+pub mod schema;
+pub mod script_expr;
+#[cfg(feature = "async-tokio")]
+pub mod supervisor;
+pub mod syntax;
+#[cfg(feature = "unblock")]
+pub mod unblock;
 
-pub use michelson::{install_parser, Parser};
+pub use codec::{Backend, Codec, NativeCodec};
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+pub use michelson::ParserBuilder;
+#[cfg(feature = "async-tokio")]
+pub use michelson::{install_parser, install_parser_in, Parser};
+pub use michelson::{ConversionError, SchemaHandle};
 pub use michelson_map::MichelsonMap;
 pub use michelson_types::*;
+#[cfg(feature = "async-tokio")]
+pub use pool::{ParserPool, PoolStats};
+#[cfg(feature = "async-tokio")]
+pub use supervisor::{ExitReport, SupervisedParser, SupervisorConfig};
 
 /// Crate's `Error` type
 #[derive(Debug)]
 pub enum Error {
     IoError(std::io::Error),
+    /// The parser process exited before responding
     ReadNone,
+    /// No response from the parser process within the timeout
+    Timeout,
     IdMismatch,
     EncodeError {
         error: serde_json::Value,
@@ -22,11 +54,29 @@ pub enum Error {
     DecodeError {
         error: serde_json::Value,
     },
+    /// An invalid `address` or `contract` value
+    InvalidAddress(Box<ConversionError>),
+    /// A map key that doesn't match the key type of the map
+    MapKeyMismatch(Box<ConversionError>),
+    /// A record field missing from the encoded value
+    MissingField(Box<ConversionError>),
+    /// Any other value not matching its type
+    InvalidValue(Box<ConversionError>),
+    /// The schema handle isn't registered in the parser process, see [`SchemaHandle`]
+    UnknownSchema,
+    /// The contract has no entrypoint with this name, see [`entrypoints`]
+    UnknownEntrypoint(String),
+    /// Two branches of a `parameter` type have the same entrypoint name
+    DuplicateEntrypoint(String),
     JsonError(serde_json::Error),
     /// Associated schema for the type is not  present, see [`JsonWrapped::SCHEMA_STR`]
     NoSchema,
     /// Errors during converting a raw [`serde_json::Value`] to a Rust data type
     EncodingError(String),
+    /// A schema that is not a valid Michelson type, see [`schema`]
+    InvalidSchema(String),
+    /// Errors of the binary (`PACK`) encoding, see [`pack`]
+    PackError(pack::PackError),
 }
 
 impl std::error::Error for Error {}
@@ -51,3 +101,9 @@ impl std::convert::From<serde_json::Error> for Error {
         Error::JsonError(error)
     }
 }
+
+impl std::convert::From<pack::PackError> for Error {
+    fn from(error: pack::PackError) -> Self {
+        Error::PackError(error)
+    }
+}
@@ -20,9 +20,11 @@ pub type Bytes = String;
 ///
 /// Note that this is a simplistic and future-proof representation,
 /// numbers as well as primitives are encoded as `String`s, like in the JSON format.
+///
+/// Besides JSON, values can be converted from and to the Michelson notation
+/// (see [`syntax`](crate::syntax)) and the binary `PACK` format (see [`pack`](crate::pack)).
 
 // TODO: simple values doesn't seem to support annotations in Micheline?
-// TODO: we could use Taquito to convert between JSON syntax and the 'normal' Micheline version, see https://github.com/zfxlabs/zfx-michelson/issues/4
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Micheline {
@@ -1,41 +1,93 @@
-//! The main decoder/encoder ([`Parser`])
+//! The main decoder/encoder (`Parser`) and its settings ([`ParserBuilder`])
+//!
+//! `Parser` requires the `async-tokio` feature (enabled by default),
+//! see also the `blocking` and `unblock` modules and their respective features.
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
 use crate::{Error, Result};
 
 use serde::{Deserialize, Serialize};
 use serde_json::value::Value;
 
-use include_dir::{include_dir, Dir};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{ChildStdin, ChildStdout, Command};
+#[cfg(feature = "async-tokio")]
+pub use crate::node::{install_parser, install_parser_in, Parser};
 
-use std::process::Stdio;
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+use std::{ffi::OsString, path::PathBuf, process::Stdio, time::Duration};
 
-use tokio::fs::File;
-
-static SCRIPTS_DIR: Dir<'_> = include_dir!("./scripts");
-static BUNDLE_NAME: &str = "michelson_parser.bundle.js";
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+pub(crate) static BUNDLE_NAME: &str = "michelson_parser.bundle.js";
 
 pub type MichelsonV1Expression = Value;
 
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
 #[derive(Clone, Debug, Serialize)]
-struct Request {
-    id: usize,
-    content: RequestContent,
+pub(crate) struct Request {
+    pub(crate) id: usize,
+    pub(crate) content: RequestContent,
 }
 
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
 #[derive(Clone, Debug, Deserialize)]
-struct Response {
-    id: usize,
-    content: ResponseContent,
+pub(crate) struct Response {
+    pub(crate) id: usize,
+    pub(crate) content: ResponseContent,
 }
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "kind")]
 pub enum RequestContent {
-    Encode { data: Value, schema: Value },
-    Decode { michelson: Value, schema: Value },
+    Encode {
+        data: Value,
+        schema: Value,
+    },
+    Decode {
+        michelson: Value,
+        schema: Value,
+    },
+    /// Several values encoded with the same schema, the response has a result for each value
+    EncodeBatch {
+        data: Vec<Value>,
+        schema: Value,
+    },
+    /// Several values decoded with the same schema, the response has a result for each value
+    DecodeBatch {
+        michelson: Vec<Value>,
+        schema: Value,
+    },
+    /// Keep a schema in the parser process, the response is its handle
+    RegisterSchema {
+        schema: Value,
+    },
+    /// Forget a registered schema, the response is `true` if it was registered
+    UnregisterSchema {
+        handle: SchemaHandle,
+    },
+    EncodeRegistered {
+        data: Value,
+        handle: SchemaHandle,
+    },
+    DecodeRegistered {
+        michelson: Value,
+        handle: SchemaHandle,
+    },
+    /// Serialise a Michelson value like the `PACK` instruction, the response is hex bytes
+    Pack {
+        data: Value,
+        schema: Value,
+    },
+    /// Deserialise packed hex bytes, the response is a Michelson value
+    Unpack {
+        bytes: String,
+        schema: Value,
+    },
 }
 
+/// A schema registered in a parser process, see `Parser::register_schema`
+///
+/// A handle is only valid for the process that returned it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SchemaHandle(u64);
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(tag = "status")]
 pub enum ResponseContent {
@@ -43,123 +95,309 @@ pub enum ResponseContent {
     Error { error: Value },
 }
 
-/// Install the necessary Javascript code in the current directory
-///
-/// This step is needed before creating a [`Parser`]
-pub async fn install_parser() {
-    let parser_js = SCRIPTS_DIR.get_file(BUNDLE_NAME).unwrap();
-    let mut file_to_deploy = File::create(BUNDLE_NAME).await.unwrap();
-    file_to_deploy
-        .write_all(parser_js.contents())
-        .await
-        .unwrap();
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+impl ResponseContent {
+    /// The result of an `Encode` request
+    pub(crate) fn encoded(self) -> Result<Value> {
+        match self {
+            ResponseContent::Success { value } => Ok(value),
+            ResponseContent::Error { error } => Err(ConversionError::from_response(error, true)),
+        }
+    }
+
+    /// The result of a `Decode` request
+    pub(crate) fn decoded(self) -> Result<Value> {
+        match self {
+            ResponseContent::Success { value } => Ok(value),
+            ResponseContent::Error { error } => Err(ConversionError::from_response(error, false)),
+        }
+    }
+
+    /// The handle returned by a `RegisterSchema` request
+    ///
+    /// Invalid schemas are reported like the errors of `Decode` requests.
+    pub(crate) fn registered(self) -> Result<SchemaHandle> {
+        Ok(serde_json::from_value(self.decoded()?)?)
+    }
+
+    /// The result of an `UnregisterSchema` request
+    pub(crate) fn unregistered(self) -> Result<bool> {
+        Ok(serde_json::from_value(self.decoded()?)?)
+    }
+
+    /// The bytes of a `Pack` request
+    pub(crate) fn packed(self) -> Result<Vec<u8>> {
+        let bytes: String = serde_json::from_value(self.encoded()?)?;
+        Ok(crate::pack::decode_hex(&bytes)?)
+    }
+
+    /// The results of an `EncodeBatch` request
+    pub(crate) fn encoded_batch(self) -> Result<Vec<Result<Value>>> {
+        let items: Vec<ResponseContent> = serde_json::from_value(self.encoded()?)?;
+        Ok(items.into_iter().map(ResponseContent::encoded).collect())
+    }
+
+    /// The results of a `DecodeBatch` request
+    pub(crate) fn decoded_batch(self) -> Result<Vec<Result<Value>>> {
+        let items: Vec<ResponseContent> = serde_json::from_value(self.decoded()?)?;
+        Ok(items.into_iter().map(ResponseContent::decoded).collect())
+    }
 }
 
-/// The main decoder/encoder backed by a NodeJS process using Taquito
-///
-/// NodeJS must be installed on the system,
-/// in particular the `node` binary must be present in the `PATH`
+/// A conversion error reported by the parser process
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ConversionError {
+    /// The class of the Taquito error, e.g. `AddressValidationError`
+    pub class: String,
+    pub message: String,
+    /// The position of the failing type in the schema: field names, `key` or `value`
+    /// for the arguments of maps, argument indices otherwise
+    ///
+    /// `None` if the position is not known.
+    pub path: Option<Vec<String>>,
+    /// The field name of the failing type
+    pub field: Option<String>,
+    /// The offending value, `None` if it's missing
+    #[serde(default)]
+    pub value: Option<Value>,
+}
+
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+impl ConversionError {
+    /// Map the `error` of a response to an [`Error`]
+    ///
+    /// Errors without the structure of [`ConversionError`] are returned as
+    /// [`Error::EncodeError`] or [`Error::DecodeError`].
+    fn from_response(error: Value, encode: bool) -> Error {
+        let conversion: ConversionError = match serde_json::from_value(error.clone()) {
+            Ok(conversion) => conversion,
+            Err(_) if encode => return Error::EncodeError { error },
+            Err(_) => return Error::DecodeError { error },
+        };
+        let map_key = conversion
+            .path
+            .as_ref()
+            .and_then(|path| path.last())
+            .is_some_and(|last| last == "key");
+        let validation = conversion.class.ends_with("ValidationError");
+        if conversion.class == "UnknownSchemaError" {
+            Error::UnknownSchema
+        } else if map_key && (validation || conversion.class == "MapTypecheckError") {
+            Error::MapKeyMismatch(Box::new(conversion))
+        } else if encode && validation && conversion.value.is_none() && conversion.field.is_some() {
+            Error::MissingField(Box::new(conversion))
+        } else if conversion.class == "AddressValidationError"
+            || conversion.class == "ContractValidationError"
+        {
+            Error::InvalidAddress(Box::new(conversion))
+        } else if validation || conversion.class == "MapTypecheckError" {
+            Error::InvalidValue(Box::new(conversion))
+        } else if encode {
+            Error::EncodeError { error }
+        } else {
+            Error::DecodeError { error }
+        }
+    }
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.class, self.message)?;
+        if let Some(path) = &self.path {
+            write!(f, " at `{}`", path.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings of the NodeJS process of a `Parser` (or a `blocking::Parser`)
 ///
-/// ## Examples
-/// See the [crate-level documentation](crate).
+/// By default the `node` binary is looked up in the `PATH`, and the bundle installed by
+/// `install_parser` is expected in the current directory.
 ///
-/// ## Panics
+/// ```no_run
+/// # async fn run() -> zfx_michelson::Result<()> {
+/// use zfx_michelson::ParserBuilder;
 ///
-/// - [`new`](Parser::new) panics if the child process cannot be started
-/// - [`encode`](Parser::encode) and [`decode`](Parser::decode) may panic
-///   on I/O error (most likely, when the child process exited)
-// TODO: unusual race conditions with concurrent calls to the same parser may cause an [`Error::IdMismatch`] result.
-// If this is observed in practice, we can match respective request and response IDs to avoid this
-
-pub struct Parser {
-    stdin: ChildStdin,
-    stdout: ChildStdout,
-    current_id: usize,
+/// let builder = ParserBuilder::new()
+///     .node("/usr/local/bin/node")
+///     .bundle("/opt/app/michelson_parser.bundle.js")
+///     .env("NODE_OPTIONS", "--max-old-space-size=512");
+/// # #[cfg(feature = "async-tokio")]
+/// let parser = builder.build()?;
+/// # #[cfg(feature = "blocking")]
+/// let parser = zfx_michelson::blocking::Parser::with_builder(&builder)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+#[derive(Clone, Debug)]
+pub struct ParserBuilder {
+    node: PathBuf,
+    bundle: PathBuf,
+    current_dir: Option<PathBuf>,
+    env: Vec<(OsString, OsString)>,
+    args: Vec<OsString>,
+    #[cfg_attr(not(feature = "async-tokio"), allow(dead_code))]
+    pub(crate) timeout: Option<Duration>,
 }
 
-impl Parser {
-    /// Create a new `Parser` instance, **this starts a new NodeJS process**
-    pub fn new() -> Parser {
-        let mut child = Command::new("node")
-            //.current_dir("./scripts")
-            .args(&[BUNDLE_NAME]) //FIXME: config
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("parser::command failed");
-        let stdin = child.stdin.take().expect("couldn't get stdin");
-        let stdout = child.stdout.take().expect("couldn't get stdout");
-        Parser {
-            stdin,
-            stdout,
-            current_id: 0,
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+impl Default for ParserBuilder {
+    fn default() -> Self {
+        ParserBuilder {
+            node: PathBuf::from("node"),
+            bundle: PathBuf::from(BUNDLE_NAME),
+            current_dir: None,
+            env: vec![],
+            args: vec![],
+            timeout: None,
         }
     }
+}
 
-    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
-    /// according to the data schema provided
-    pub async fn encode(
-        &mut self,
-        data: Value,
-        schema: MichelsonV1Expression,
-    ) -> Result<MichelsonV1Expression> {
-        let id = self.current_id;
-        self.current_id += 1;
-        let content = RequestContent::Encode { data, schema };
-        submit(&mut self.stdin, id, content).await;
-        let encoded_response = receive(&mut self.stdout).await.unwrap();
-        if encoded_response.id != id {
-            return Err(Error::IdMismatch);
-        };
-        match encoded_response.content {
-            ResponseContent::Success { value } => Ok(value),
-            ResponseContent::Error { error } => Err(Error::EncodeError { error }),
-        }
+#[cfg(any(feature = "async-tokio", feature = "blocking"))]
+impl ParserBuilder {
+    pub fn new() -> ParserBuilder {
+        ParserBuilder::default()
+    }
+
+    /// The NodeJS executable, either a path or a name looked up in the `PATH`
+    pub fn node(mut self, node: impl Into<PathBuf>) -> Self {
+        self.node = node.into();
+        self
     }
 
-    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
-    /// according to the data schema provided
-    pub async fn decode(
-        &mut self,
-        michelson: MichelsonV1Expression,
-        schema: MichelsonV1Expression,
-    ) -> Result<Value> {
-        let id = self.current_id;
-        self.current_id += 1;
-        let content = RequestContent::Decode { michelson, schema };
-        submit(&mut self.stdin, id, content).await;
-        let decoded_response = receive(&mut self.stdout).await.unwrap();
-        if decoded_response.id != id {
-            return Err(Error::IdMismatch);
+    /// The location of the parser bundle, relative paths are resolved from the working directory
+    pub fn bundle(mut self, bundle: impl Into<PathBuf>) -> Self {
+        self.bundle = bundle.into();
+        self
+    }
+
+    /// The working directory of the process, the current directory by default
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Set an environment variable of the process, e.g. `NODE_OPTIONS`
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add an argument to `node`, these are passed before the bundle path
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add arguments to `node`, see [`arg`](ParserBuilder::arg)
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// The default timeout of the requests, none by default
+    ///
+    /// See also `Parser::encode_with_timeout` and `Parser::decode_with_timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The command starting the NodeJS process, with `stdin` and `stdout` piped
+    pub(crate) fn command(&self) -> Result<std::process::Command> {
+        let bundle = match &self.current_dir {
+            Some(dir) => dir.join(&self.bundle),
+            None => self.bundle.clone(),
         };
-        match decoded_response.content {
-            ResponseContent::Success { value } => Ok(value),
-            ResponseContent::Error { error } => Err(Error::DecodeError { error }),
+        if !bundle.is_file() {
+            return Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("parser bundle not found: {}", bundle.display()),
+            )));
         }
+        let mut command = std::process::Command::new(&self.node);
+        command
+            .args(&self.args)
+            .arg(&self.bundle)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        Ok(command)
     }
-
-    // TODO `drop`: technically we'd need to kill the child process explicitly,
-    // or verify whether the NodeJS process exits cleanly on closing `stdin`
 }
 
-async fn submit(stdin: &mut ChildStdin, id: usize, content: RequestContent) {
-    let request = Request { id, content };
-    let encoded = serde_json::to_string(&request).expect("Failed to encode request to JSON");
-    let payload = format!("{}\n", encoded);
-    stdin
-        .write_all(&payload.as_bytes())
-        .await
-        .expect("stdin - Write failed");
-    stdin.flush().await.expect("stdin - flush failed");
-}
+#[cfg(all(test, any(feature = "async-tokio", feature = "blocking")))]
+mod test {
+    use super::*;
+    use serde_json::json;
 
-async fn receive(stdout: &mut ChildStdout) -> Result<Response> {
-    let mut reader = BufReader::new(stdout).lines();
+    fn encode_error(error: Value) -> Error {
+        ResponseContent::Error { error }.encoded().unwrap_err()
+    }
 
-    if let Ok(Some(line)) = reader.next_line().await {
-        let response: Response = serde_json::from_str(&line).expect("unable to decode json");
-        Ok(response)
-    } else {
-        Err(Error::ReadNone)
+    #[test]
+    fn test_conversion_errors() {
+        let error = |class: &str, path: Value, field: Value| json!({ "class": class, "message": "", "path": path, "field": field });
+        let mut address = error("AddressValidationError", json!(["owner"]), json!("owner"));
+        address["value"] = json!("tz1");
+        assert!(matches!(encode_error(address), Error::InvalidAddress(_)));
+
+        let mut key = error(
+            "AddressValidationError",
+            json!(["balances", "key"]),
+            json!(null),
+        );
+        key["value"] = json!("tz1");
+        assert!(matches!(encode_error(key), Error::MapKeyMismatch(_)));
+        let key = error("MapTypecheckError", json!(["0", "key"]), json!(null));
+        assert!(matches!(encode_error(key), Error::MapKeyMismatch(_)));
+
+        let missing = error(
+            "NatValidationError",
+            json!(["1", "amount"]),
+            json!("amount"),
+        );
+        match encode_error(missing) {
+            Error::MissingField(error) => {
+                assert_eq!(error.field.as_deref(), Some("amount"));
+                assert_eq!(error.value, None);
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let mut invalid = error("ValidationError", json!(null), json!(null));
+        invalid["value"] = json!("x");
+        match encode_error(invalid) {
+            Error::InvalidValue(error) => {
+                assert_eq!(error.path, None);
+                assert_eq!(error.to_string(), "ValidationError: ");
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let unknown = error("UnknownSchemaError", json!(null), json!(null));
+        assert!(matches!(encode_error(unknown), Error::UnknownSchema));
+
+        let other = error("TypeError", json!(null), json!(null));
+        assert!(matches!(encode_error(other), Error::EncodeError { .. }));
+        assert!(matches!(
+            encode_error(json!("oops")),
+            Error::EncodeError { .. }
+        ));
+        let decoded = ResponseContent::Error {
+            error: json!("oops"),
+        }
+        .decoded();
+        assert!(matches!(decoded, Err(Error::DecodeError { .. })));
     }
 }
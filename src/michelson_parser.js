@@ -1,8 +1,14 @@
 "use strict";
 
 const { pipeline, Transform } = require("stream");
-const { inspect } = require("util");
-const { jsonEncode, jsonDecode } = require("./json_converter");
+const { packDataBytes, unpackDataBytes } = require("@taquito/michel-codec");
+const {
+  jsonEncode,
+  jsonDecode,
+  jsonEncoder,
+  jsonDecoder,
+  jsonError,
+} = require("./json_converter");
 
 const devMode = process.env.NODE_ENV === "development";
 
@@ -56,7 +62,12 @@ const read = (callback) => {
       return done();
     },
   });
-  const errorHandler = (err = "close") => failure(err);
+  const errorHandler = (err) => {
+    if (err) {
+      failure(err);
+    }
+    // Otherwise `stdin` was closed: exit once the pending responses are written
+  };
 
   pipeline(process.stdin, parseStream, errorHandler).on("data", callback);
 };
@@ -73,11 +84,106 @@ const onDecode = async (id, content) => {
   respond(id, { status: "Success", value });
 };
 
+const onPack = async (id, content) => {
+  const { schema, data } = content;
+  const value = packDataBytes(data, schema).bytes;
+  respond(id, { status: "Success", value });
+};
+
+const onUnpack = async (id, content) => {
+  const { schema, bytes } = content;
+  const value = unpackDataBytes({ bytes }, schema);
+  respond(id, { status: "Success", value });
+};
+
+// Schemas registered by `RegisterSchema` requests, by handle
+const schemas = new Map();
+let nextHandle = 0;
+
+const registered = (handle) => {
+  const entry = schemas.get(handle);
+  if (entry === undefined) {
+    const err = new Error("unknown schema handle: " + JSON.stringify(handle));
+    err.name = "UnknownSchemaError";
+    throw err;
+  }
+  return entry;
+};
+
+// The schema of a request, for error reports
+const requestSchema = (content) =>
+  content.handle !== undefined
+    ? (schemas.get(content.handle) || {}).schema
+    : content.schema;
+
+const onRegisterSchema = async (id, content) => {
+  const { schema } = content;
+  const entry = { schema, encode: jsonEncoder(schema), decode: jsonDecoder(schema) };
+  const value = nextHandle++;
+  schemas.set(value, entry);
+  respond(id, { status: "Success", value });
+};
+
+const onUnregisterSchema = async (id, content) => {
+  const value = schemas.delete(content.handle);
+  respond(id, { status: "Success", value });
+};
+
+const onEncodeRegistered = async (id, content) => {
+  const { handle, data } = content;
+  const value = registered(handle).encode(data);
+  respond(id, { status: "Success", value });
+};
+
+const onDecodeRegistered = async (id, content) => {
+  const { handle, michelson } = content;
+  const value = registered(handle).decode(michelson);
+  respond(id, { status: "Success", value });
+};
+
+// Convert each item separately, a failing item doesn't fail the whole batch
+const batch = (schema, items, convert) =>
+  items.map((item) => {
+    try {
+      return { status: "Success", value: convert(item) };
+    } catch (err) {
+      return { status: "Error", error: jsonError(schema, err) };
+    }
+  });
+
+const onEncodeBatch = async (id, content) => {
+  const { schema, data } = content;
+  const value = batch(schema, data, jsonEncoder(schema));
+  respond(id, { status: "Success", value });
+};
+
+const onDecodeBatch = async (id, content) => {
+  const { schema, michelson } = content;
+  const value = batch(schema, michelson, jsonDecoder(schema));
+  respond(id, { status: "Success", value });
+};
+
 const onRequest = (id, content) => {
   if (content.kind === "Encode") {
     return onEncode(id, content);
   } else if (content.kind === "Decode") {
     return onDecode(id, content);
+  } else if (content.kind === "EncodeBatch") {
+    return onEncodeBatch(id, content);
+  } else if (content.kind === "DecodeBatch") {
+    return onDecodeBatch(id, content);
+  } else if (content.kind === "RegisterSchema") {
+    return onRegisterSchema(id, content);
+  } else if (content.kind === "UnregisterSchema") {
+    return onUnregisterSchema(id, content);
+  } else if (content.kind === "EncodeRegistered") {
+    return onEncodeRegistered(id, content);
+  } else if (content.kind === "DecodeRegistered") {
+    return onDecodeRegistered(id, content);
+  } else if (content.kind === "Pack") {
+    return onPack(id, content);
+  } else if (content.kind === "Unpack") {
+    return onUnpack(id, content);
   } else {
     failure(new Error("invalid content.kind: " + JSON.stringify(content.kind)));
   }
@@ -88,7 +194,7 @@ read((request) => {
   onRequest(id, content)
     .catch((err) => {
       const status = "Error";
-      const error = inspect(err);
+      const error = jsonError(requestSchema(content), err);
       respond(id, { status, error });
     })
     .catch(failure);
@@ -0,0 +1,906 @@
+//! Native conversion between Michelson values and Taquito-style JSON, without NodeJS
+//!
+//! [`decode`] produces the same JSON as the Taquito-based `Parser::decode`
+//! (see `jsonDecode` in `src/json_converter.js`), and [`encode`] accepts the same JSON as
+//! `Parser::encode` (see `jsonEncode`):
+//!
+//! - records (`pair`s) become objects, fields are named after their annotation,
+//!   or after their position if they are not annotated;
+//!   nested pairs without annotation are flattened into the enclosing record
+//! - variants (`or`s) become singleton objects `{ "variant": value }`,
+//!   variants carrying `unit` become `{ "__enum__": "Variant" }`
+//!   (the first letter is upper-cased, and lower-cased again when encoding)
+//! - `map`s and `big_map`s become `{ "MichelsonMap": { "key": value, ... } }`,
+//!   `big_map` identifiers are returned as a number
+//! - `unit` becomes `{ "__unit__": null }`
+//! - numbers (`int`, `nat`, `mutez`) are returned as `String`s
+//! - `option`s become `null` or the contained value
+//! - addresses, keys, signatures and chain IDs are returned in their readable (Base58) form,
+//!   timestamps as ISO 8601 strings
+//!
+//! The output is identical to the Taquito path for the fixtures of
+//! `tests/mocha/conversion_tests.spec.js`, which are run against this module by
+//! `tests/native_conversion.rs`. It deviates only for values that Taquito cannot convert
+//! to usable JSON, and that none of these fixtures contain:
+//!
+//! - maps nested in lists, options or other maps are wrapped like the maps in record fields;
+//!   Taquito only wraps those, the fixtures (e.g. `test_register_storage`) have no other maps
+//! - non-scalar map keys (`pair`, `or`, `option`) are serialised as JSON; Taquito uses
+//!   `toString`, e.g. `[object Object]`, the keys of the fixtures are addresses and strings
+//! - only `or` variants are turned into enums; Taquito turns any object with a single `unit`
+//!   field into one, e.g. a `map string unit` with one key, the fixtures only have `or` enums
+//!
+//! Unlike Taquito, [`encode`] rejects `set` elements and `map` keys with the same value
+//! (e.g. `"01"` and `"1"` for a `nat`), which would produce an invalid Michelson value.
+use crate::encoding;
+use crate::micheline::Micheline;
+use crate::pack::parse_number;
+use crate::{Error, Result};
+
+use serde_json::{json, Map, Value};
+
+/// Decode a Michelson value to Taquito-style JSON, according to the schema (a Michelson type)
+pub fn decode(michelson: &Micheline, schema: &Micheline) -> Result<Value> {
+    execute(schema, 0, michelson)
+}
+
+fn decode_error(message: String) -> Error {
+    Error::DecodeError {
+        error: Value::String(message),
+    }
+}
+
+fn unexpected(schema: &Micheline, value: &Micheline) -> Error {
+    decode_error(format!(
+        "unexpected value for type `{}`: {}",
+        type_name(schema),
+        value.to_michelson()
+    ))
+}
+
+/// The primitive, arguments and annotations of a type
+pub(crate) fn as_type(schema: &Micheline) -> Result<(&str, &[Micheline], &[String])> {
+    match schema {
+        Micheline::Prim { prim, args, annots } => Ok((prim, args, annots)),
+        _ => Err(Error::EncodingError(format!(
+            "invalid schema: {}",
+            schema.to_michelson()
+        ))),
+    }
+}
+
+fn type_name(schema: &Micheline) -> &str {
+    match schema {
+        Micheline::Prim { prim, .. } => prim,
+        _ => "?",
+    }
+}
+
+pub(crate) fn is_type(schema: &Micheline, name: &str) -> bool {
+    type_name(schema) == name
+}
+
+pub(crate) fn has_annots(schema: &Micheline) -> bool {
+    matches!(schema, Micheline::Prim { annots, .. } if !annots.is_empty())
+}
+
+/// The name of a field or variant: its first annotation without the `%` or `:`,
+/// or its position if it has no annotation, like Taquito's `Token.annot`
+pub(crate) fn annot(schema: &Micheline, idx: usize) -> String {
+    match schema {
+        Micheline::Prim { annots, .. } if !annots.is_empty() => {
+            let a = &annots[0];
+            match a.find(['%', ':']) {
+                Some(i) => {
+                    let rest = &a[i + 1..];
+                    let rest = rest.strip_prefix("_Liq_entry_").unwrap_or(rest);
+                    format!("{}{}", &a[..i], rest)
+                }
+                None => a.clone(),
+            }
+        }
+        _ => idx.to_string(),
+    }
+}
+
+fn arg(schema: &Micheline, i: usize) -> Result<&Micheline> {
+    let (prim, args, _) = as_type(schema)?;
+    args.get(i).ok_or_else(|| {
+        Error::EncodingError(format!("invalid schema: missing argument of `{}`", prim))
+    })
+}
+
+/// Binary view of a pair with two or more arguments (right combs)
+fn collapse(prim: &str, args: &[Micheline]) -> Option<[Micheline; 2]> {
+    match args {
+        [a, b] => Some([a.clone(), b.clone()]),
+        [a, rest @ ..] if rest.len() > 1 => Some([
+            a.clone(),
+            Micheline::Prim {
+                prim: prim.to_owned(),
+                args: rest.to_vec(),
+                annots: vec![],
+            },
+        ]),
+        _ => None,
+    }
+}
+
+fn collapse_type(schema: &Micheline) -> Result<[Micheline; 2]> {
+    let (prim, args, _) = as_type(schema)?;
+    collapse(prim, args)
+        .ok_or_else(|| Error::EncodingError(format!("invalid schema: {}", schema.to_michelson())))
+}
+
+pub(crate) fn collapse_value(schema: &Micheline, value: &Micheline) -> Result<[Micheline; 2]> {
+    let pair = match value {
+        Micheline::Prim { prim, args, .. } if prim == "Pair" => collapse("Pair", args),
+        Micheline::Seq(items) => collapse("Pair", items),
+        _ => None,
+    };
+    pair.ok_or_else(|| unexpected(schema, value))
+}
+
+/// The two components of a `pair` type with the positions used to name them
+pub(crate) fn pair_fields(schema: &Micheline, idx: usize) -> Result<[(Micheline, usize); 2]> {
+    let [left, right] = collapse_type(schema)?;
+    let left_count = if is_type(&left, "pair") {
+        field_names(&left, idx)?.len()
+    } else {
+        1
+    };
+    Ok([(left, idx), (right, idx + left_count)])
+}
+
+/// The two branches of an `or` type with the positions used to name them
+pub(crate) fn or_branches(schema: &Micheline, idx: usize) -> Result<[(Micheline, usize); 2]> {
+    let left = arg(schema, 0)?.clone();
+    let right = arg(schema, 1)?.clone();
+    let left_count = if is_type(&left, "or") {
+        field_names(&left, idx)?.len()
+    } else {
+        1
+    };
+    Ok([(left, idx), (right, idx + left_count)])
+}
+
+/// Names of the fields of a record (flattened `pair`s) or the variants of an `or` tree
+pub(crate) fn field_names(schema: &Micheline, idx: usize) -> Result<Vec<String>> {
+    let is_pair = is_type(schema, "pair");
+    let (fields, nested_type) = if is_pair {
+        (collapse_type(schema)?, "pair")
+    } else {
+        ([arg(schema, 0)?.clone(), arg(schema, 1)?.clone()], "or")
+    };
+    let mut names: Vec<String> = vec![];
+    let mut i = idx;
+    for field in fields.iter() {
+        let nested = if is_type(field, nested_type) {
+            Some(field_names(field, i)?)
+        } else {
+            None
+        };
+        let count = nested.as_ref().map_or(1, Vec::len);
+        let new_names = match nested {
+            Some(nested) if !(is_pair && has_annots(field)) => nested,
+            _ => vec![annot(field, i)],
+        };
+        for name in new_names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        i += count;
+    }
+    Ok(names)
+}
+
+pub(crate) fn first_to_upper(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn execute(schema: &Micheline, idx: usize, value: &Micheline) -> Result<Value> {
+    let (prim, _, _) = as_type(schema)?;
+    match (prim, value) {
+        ("pair", _) => {
+            let values = collapse_value(schema, value)?;
+            let mut record = Map::new();
+            for ((field, i), v) in pair_fields(schema, idx)?.iter().zip(values.iter()) {
+                let decoded = execute(field, *i, v)?;
+                match decoded {
+                    Value::Object(fields) if is_type(field, "pair") && !has_annots(field) => {
+                        record.extend(fields)
+                    }
+                    decoded => {
+                        record.insert(annot(field, *i), decoded);
+                    }
+                }
+            }
+            Ok(Value::Object(record))
+        }
+        ("or", Micheline::Prim { prim, args, .. }) if args.len() == 1 => {
+            let [left, right] = or_branches(schema, idx)?;
+            let (branch, i) = match prim.as_str() {
+                "Left" => left,
+                "Right" => right,
+                _ => return Err(unexpected(schema, value)),
+            };
+            let decoded = execute(&branch, i, &args[0])?;
+            if is_type(&branch, "or") {
+                Ok(decoded)
+            } else if decoded == json!({ "__unit__": null }) {
+                Ok(json!({ "__enum__": first_to_upper(&annot(&branch, i)) }))
+            } else {
+                Ok(json!({ annot(&branch, i): decoded }))
+            }
+        }
+        ("option", Micheline::Prim { prim, args, .. }) => match (prim.as_str(), args.as_slice()) {
+            ("None", []) => Ok(Value::Null),
+            ("Some", [v]) => execute(arg(schema, 0)?, 0, v),
+            _ => Err(unexpected(schema, value)),
+        },
+        ("list" | "set", Micheline::Seq(items)) => {
+            let elt = arg(schema, 0)?;
+            let decoded: Result<Vec<Value>> = items.iter().map(|v| execute(elt, 0, v)).collect();
+            Ok(Value::Array(decoded?))
+        }
+        ("map" | "big_map", Micheline::Seq(items)) => {
+            let mut map = Map::new();
+            for item in items {
+                match item {
+                    Micheline::Prim { prim, args, .. } if prim == "Elt" && args.len() == 2 => {
+                        let key = map_key(arg(schema, 0)?, &args[0])?;
+                        map.insert(key, execute(arg(schema, 1)?, 0, &args[1])?);
+                    }
+                    _ => return Err(unexpected(schema, item)),
+                }
+            }
+            Ok(json!({ "MichelsonMap": map }))
+        }
+        ("big_map" | "sapling_state", Micheline::Number { int })
+        | ("int" | "nat" | "mutez" | "bls12_381_fr", Micheline::Number { int }) => {
+            let n = parse_number(int).map_err(|_| unexpected(schema, value))?;
+            Ok(Value::String(n.to_string()))
+        }
+        ("string" | "operation", Micheline::String { string }) => Ok(Value::String(string.clone())),
+        (
+            "bytes"
+            | "bls12_381_g1"
+            | "bls12_381_g2"
+            | "bls12_381_fr"
+            | "chest"
+            | "chest_key"
+            | "sapling_transaction"
+            | "sapling_transaction_deprecated",
+            Micheline::Bytes { bytes },
+        ) => Ok(Value::String(bytes.clone())),
+        ("bool", Micheline::Prim { prim, .. }) => match prim.as_str() {
+            "True" => Ok(Value::Bool(true)),
+            "False" => Ok(Value::Bool(false)),
+            _ => Err(unexpected(schema, value)),
+        },
+        ("unit", Micheline::Prim { prim, args, .. }) if prim == "Unit" && args.is_empty() => {
+            Ok(json!({ "__unit__": null }))
+        }
+        (
+            "address"
+            | "contract"
+            | "key_hash"
+            | "key"
+            | "signature"
+            | "chain_id"
+            | "tx_rollup_l2_address",
+            Micheline::String { string },
+        ) => Ok(Value::String(string.clone())),
+        (
+            "address"
+            | "contract"
+            | "key_hash"
+            | "key"
+            | "signature"
+            | "chain_id"
+            | "tx_rollup_l2_address",
+            Micheline::Bytes { bytes },
+        ) => {
+            let bytes = crate::pack::decode_hex(bytes).map_err(|_| unexpected(schema, value))?;
+            let readable = match prim {
+                "address" | "contract" => encoding::encode_address(&bytes),
+                "key_hash" => encoding::encode_key_hash(&bytes),
+                "key" => encoding::encode_key(&bytes),
+                "signature" => encoding::encode_signature(&bytes),
+                "chain_id" => encoding::encode_chain_id(&bytes),
+                _ => (bytes.len() == 20)
+                    .then(|| encoding::b58check_encode(encoding::PREFIX_TZ4, &bytes)),
+            };
+            readable
+                .map(Value::String)
+                .ok_or_else(|| unexpected(schema, value))
+        }
+        ("timestamp", Micheline::String { string }) => {
+            let millis = if !string.is_empty() && string.bytes().all(|c| c.is_ascii_digit()) {
+                string
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|seconds| seconds.checked_mul(1000))
+            } else {
+                encoding::parse_timestamp_millis(string)
+            };
+            let millis = millis.ok_or_else(|| unexpected(schema, value))?;
+            Ok(Value::String(encoding::format_timestamp_millis(millis)))
+        }
+        ("timestamp", Micheline::Number { int }) => {
+            let seconds = int.parse().map_err(|_| unexpected(schema, value))?;
+            Ok(Value::String(encoding::format_timestamp(seconds)))
+        }
+        ("lambda", Micheline::String { string }) => Ok(Value::String(string.clone())),
+        ("lambda", _) => Ok(serde_json::to_value(value)?),
+        ("ticket" | "ticket_deprecated", _) => {
+            let [ticketer, rest] = collapse_value(schema, value)?;
+            let [contents, amount] = collapse_value(schema, &rest)?;
+            let address = Micheline::Prim {
+                prim: "address".to_owned(),
+                args: vec![],
+                annots: vec![],
+            };
+            let int = Micheline::Prim {
+                prim: "int".to_owned(),
+                args: vec![],
+                annots: vec![],
+            };
+            Ok(json!({
+                "ticketer": execute(&address, 0, &ticketer)?,
+                "value": execute(arg(schema, 0)?, 0, &contents)?,
+                "amount": execute(&int, 0, &amount)?,
+            }))
+        }
+        _ => Err(unexpected(schema, value)),
+    }
+}
+
+/// Map keys are converted to strings, like `toString` would do in Javascript
+fn map_key(schema: &Micheline, key: &Micheline) -> Result<String> {
+    match execute(schema, 0, key)? {
+        Value::String(s) => Ok(s),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Encode Taquito-style JSON to a Michelson value, according to the schema (a Michelson type)
+///
+/// This is the inverse of [`decode`], `big_map` identifiers are accepted as well.
+pub fn encode(data: &Value, schema: &Micheline) -> Result<Micheline> {
+    encode_object(schema, 0, Some(data))
+}
+
+fn encode_error(message: String) -> Error {
+    Error::EncodeError {
+        error: Value::String(message),
+    }
+}
+
+fn invalid(schema: &Micheline, value: &Value) -> Error {
+    encode_error(format!(
+        "invalid value for type `{}`: {}",
+        type_name(schema),
+        value
+    ))
+}
+
+pub(crate) fn first_to_lower(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn prim(prim: &str, args: Vec<Micheline>) -> Micheline {
+    Micheline::Prim {
+        prim: prim.to_owned(),
+        args,
+        annots: vec![],
+    }
+}
+
+/// `value` is `None` for missing record fields
+fn encode_object(schema: &Micheline, idx: usize, value: Option<&Value>) -> Result<Micheline> {
+    let (prim_name, _, _) = as_type(schema)?;
+    let value = match (prim_name, value) {
+        ("option", None) => return Ok(prim("None", vec![])),
+        ("unit", _) => return Ok(prim("Unit", vec![])),
+        (_, Some(value)) => value,
+        (_, None) => {
+            return Err(encode_error(format!(
+                "missing field `{}` of type `{}`",
+                annot(schema, idx),
+                prim_name
+            )))
+        }
+    };
+    let err = || invalid(schema, value);
+    match (prim_name, value) {
+        ("pair", _) => {
+            let mut args = vec![];
+            for (field, i) in pair_fields(schema, idx)?.iter() {
+                let field_value = if is_type(field, "pair") && !has_annots(field) {
+                    Some(value)
+                } else {
+                    value.get(annot(field, *i))
+                };
+                args.push(encode_object(field, *i, field_value)?);
+            }
+            Ok(prim("Pair", args))
+        }
+        ("or", _) => encode_or(schema, idx, value)?.ok_or_else(|| {
+            encode_error(format!(
+                "no variant of `or` matches {}, expected one of {:?}",
+                value,
+                field_names(schema, idx).unwrap_or_default()
+            ))
+        }),
+        ("option", Value::Null) => Ok(prim("None", vec![])),
+        ("option", _) => Ok(prim(
+            "Some",
+            vec![encode_object(arg(schema, 0)?, 0, Some(value))?],
+        )),
+        ("list", Value::Array(items)) => {
+            let elt = arg(schema, 0)?;
+            let encoded: Result<Vec<Micheline>> = items
+                .iter()
+                .map(|v| encode_object(elt, 0, Some(v)))
+                .collect();
+            Ok(Micheline::Seq(encoded?))
+        }
+        ("set", Value::Array(items)) => {
+            let elt = arg(schema, 0)?;
+            let mut items: Vec<&Value> = items.iter().collect();
+            items.sort_by(|a, b| compare(elt, a, b));
+            let mut encoded = vec![];
+            for (i, item) in items.iter().enumerate() {
+                encoded.push(encode_object(elt, 0, Some(item))?);
+                if i > 0 && compare(elt, items[i - 1], item).is_eq() {
+                    return Err(duplicate(schema, item));
+                }
+            }
+            Ok(Micheline::Seq(encoded))
+        }
+        ("map" | "big_map", Value::Object(wrapper)) => {
+            let map = match wrapper.get("MichelsonMap") {
+                Some(Value::Object(map)) if wrapper.len() == 1 => map,
+                _ => return Err(err()),
+            };
+            let key_type = arg(schema, 0)?;
+            let mut keys = vec![];
+            for (k, v) in map.iter() {
+                keys.push((map_key_value(key_type, k), v));
+            }
+            keys.sort_by(|(a, _), (b, _)| compare(key_type, a, b));
+            let mut elts = vec![];
+            for (i, (k, v)) in keys.iter().enumerate() {
+                let key = encode_object(key_type, 0, Some(k))?;
+                if i > 0 && compare(key_type, &keys[i - 1].0, k).is_eq() {
+                    return Err(duplicate(schema, k));
+                }
+                let value = encode_object(arg(schema, 1)?, 0, Some(v))?;
+                elts.push(prim("Elt", vec![key, value]));
+            }
+            Ok(Micheline::Seq(elts))
+        }
+        ("int" | "nat" | "mutez" | "big_map" | "bls12_381_fr", _) if !value.is_object() => {
+            let int = match value {
+                Value::String(s) => parse_number(s).map_err(|_| err())?,
+                Value::Number(n) if n.is_i64() || n.is_u64() => {
+                    n.to_string().parse().map_err(|_| err())?
+                }
+                _ => return Err(err()),
+            };
+            if prim_name != "int" && int.sign() == num_bigint::Sign::Minus {
+                return Err(err());
+            }
+            Ok(Micheline::Number {
+                int: int.to_string(),
+            })
+        }
+        ("string" | "operation", Value::String(s)) => Ok(Micheline::String { string: s.clone() }),
+        (
+            "bytes"
+            | "bls12_381_g1"
+            | "bls12_381_g2"
+            | "bls12_381_fr"
+            | "chest"
+            | "chest_key"
+            | "sapling_transaction"
+            | "sapling_transaction_deprecated",
+            Value::String(s),
+        ) => {
+            if s.len() % 2 == 1 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return Err(err());
+            }
+            Ok(Micheline::Bytes { bytes: s.clone() })
+        }
+        ("bool", Value::Bool(b)) => Ok(prim(if *b { "True" } else { "False" }, vec![])),
+        ("bool", Value::String(s)) if s == "true" || s == "false" => {
+            Ok(prim(if s == "true" { "True" } else { "False" }, vec![]))
+        }
+        (
+            "address"
+            | "contract"
+            | "key_hash"
+            | "key"
+            | "signature"
+            | "chain_id"
+            | "tx_rollup_l2_address",
+            Value::String(s),
+        ) => {
+            let valid = match prim_name {
+                "address" | "contract" => encoding::decode_address(s).is_some(),
+                "key_hash" => encoding::decode_key_hash(s).is_some(),
+                "key" => encoding::decode_key(s).is_some(),
+                "signature" => encoding::decode_signature(s).is_some(),
+                "chain_id" => encoding::decode_chain_id(s).is_some(),
+                _ => encoding::b58check_decode(s, encoding::PREFIX_TZ4, 20).is_some(),
+            };
+            if valid {
+                Ok(Micheline::String { string: s.clone() })
+            } else {
+                Err(err())
+            }
+        }
+        ("timestamp", Value::String(s)) => Ok(Micheline::String { string: s.clone() }),
+        ("timestamp", Value::Number(n)) if n.is_i64() => {
+            Ok(Micheline::Number { int: n.to_string() })
+        }
+        ("lambda", _) => Micheline::from_json_value(value.clone()).map_err(|_| err()),
+        _ => Err(err()),
+    }
+}
+
+/// Two `set` elements or `map` keys with the same value, e.g. `"01"` and `"1"`
+fn duplicate(schema: &Micheline, value: &Value) -> Error {
+    encode_error(format!(
+        "duplicate element of type `{}`: {}",
+        type_name(schema),
+        value
+    ))
+}
+
+/// Encode an `or` value, returns `None` if no variant matches
+fn encode_or(schema: &Micheline, idx: usize, value: &Value) -> Result<Option<Micheline>> {
+    let unit = json!({ "__unit__": null });
+    let (label, inner) = match value {
+        Value::Object(obj) if obj.len() == 1 => {
+            let (label, inner) = obj.iter().next().expect("singleton object");
+            match (label.as_str(), inner) {
+                ("__enum__", Value::String(variant)) => (first_to_lower(variant), &unit),
+                _ => (label.clone(), inner),
+            }
+        }
+        _ => return Err(invalid(schema, value)),
+    };
+    let [left, right] = or_branches(schema, idx)?;
+    for (side, (branch, i)) in [("Left", &left), ("Right", &right)] {
+        if !is_type(branch, "or") && annot(branch, *i) == label {
+            let encoded = encode_object(branch, *i, Some(inner))?;
+            return Ok(Some(prim(side, vec![encoded])));
+        }
+    }
+    for (side, (branch, i)) in [("Left", &left), ("Right", &right)] {
+        if is_type(branch, "or") {
+            if let Some(encoded) = encode_or(branch, *i, value)? {
+                return Ok(Some(prim(side, vec![encoded])));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The JSON value of a map key, the inverse of [`map_key`]
+fn map_key_value(schema: &Micheline, key: &str) -> Value {
+    match type_name(schema) {
+        "pair" | "or" | "option" => {
+            serde_json::from_str(key).unwrap_or(Value::String(key.to_owned()))
+        }
+        _ => Value::String(key.to_owned()),
+    }
+}
+
+/// The label and the value of the variant of an `or` value, see [`encode_or`]
+fn or_variant(value: &Value) -> Option<(String, Value)> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => {
+            let (label, inner) = obj.iter().next()?;
+            match (label.as_str(), inner) {
+                ("__enum__", Value::String(variant)) => {
+                    Some((first_to_lower(variant), json!({ "__unit__": null })))
+                }
+                _ => Some((label.clone(), inner.clone())),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The sides (`false` for `Left`) leading to the branch labelled `label` of an `or` type,
+/// with the type of that branch, searched in the same order as [`encode_or`]
+fn or_path(schema: &Micheline, idx: usize, label: &str) -> Option<(Vec<bool>, Micheline)> {
+    let [left, right] = or_branches(schema, idx).ok()?;
+    for (side, (branch, i)) in [(false, &left), (true, &right)] {
+        if !is_type(branch, "or") && annot(branch, *i) == label {
+            return Some((vec![side], branch.clone()));
+        }
+    }
+    for (side, (branch, i)) in [(false, &left), (true, &right)] {
+        if is_type(branch, "or") {
+            if let Some((mut path, leaf)) = or_path(branch, *i, label) {
+                path.insert(0, side);
+                return Some((path, leaf));
+            }
+        }
+    }
+    None
+}
+
+/// Order of comparable values, used for sorting `set` elements and `map` keys
+///
+/// Like Michelson, domain-specific types are ordered by their binary form,
+/// and timestamps by their value whether they're written as strings or numbers.
+fn compare(schema: &Micheline, a: &Value, b: &Value) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let number = |v: &Value| match v {
+        Value::String(s) => parse_number(s).ok(),
+        Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    };
+    let millis = |v: &Value| match v {
+        Value::Number(n) => n.as_i64().and_then(|seconds| seconds.checked_mul(1000)),
+        Value::String(s) if !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit()) => s
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| seconds.checked_mul(1000)),
+        Value::String(s) => encoding::parse_timestamp_millis(s),
+        _ => None,
+    };
+    let binary = |decode: fn(&str) -> Option<Vec<u8>>| {
+        let bytes = |v: &Value| v.as_str().and_then(decode);
+        bytes(a)
+            .cmp(&bytes(b))
+            .then_with(|| a.as_str().cmp(&b.as_str()))
+    };
+    match type_name(schema) {
+        "int" | "nat" | "mutez" => number(a).cmp(&number(b)),
+        "bool" => a
+            .as_bool()
+            .cmp(&b.as_bool())
+            .then_with(|| a.to_string().cmp(&b.to_string())),
+        "timestamp" => millis(a)
+            .cmp(&millis(b))
+            .then_with(|| a.to_string().cmp(&b.to_string())),
+        "address" => binary(encoding::decode_address),
+        "key_hash" => binary(encoding::decode_key_hash),
+        "key" => binary(encoding::decode_key),
+        "signature" => binary(encoding::decode_signature),
+        "chain_id" => binary(encoding::decode_chain_id),
+        "or" => {
+            let branch = |v: &Value| {
+                let (label, inner) = or_variant(v)?;
+                let (path, leaf) = or_path(schema, 0, &label)?;
+                Some((path, leaf, inner))
+            };
+            match (branch(a), branch(b)) {
+                (Some((x, leaf, a)), Some((y, _, b))) if x == y => compare(&leaf, &a, &b),
+                (Some((x, ..)), Some((y, ..))) => x.cmp(&y),
+                _ => a.to_string().cmp(&b.to_string()),
+            }
+        }
+        "option" => match (a, b) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Less,
+            (_, Value::Null) => Ordering::Greater,
+            _ => arg(schema, 0).map_or(Ordering::Equal, |t| compare(t, a, b)),
+        },
+        "pair" => {
+            let fields = match pair_fields(schema, 0) {
+                Ok(fields) => fields,
+                Err(_) => return Ordering::Equal,
+            };
+            for (field, i) in fields.iter() {
+                let (x, y) = if is_type(field, "pair") && !has_annots(field) {
+                    (a, b)
+                } else {
+                    let name = annot(field, *i);
+                    (
+                        a.get(&name).unwrap_or(&Value::Null),
+                        b.get(&name).unwrap_or(&Value::Null),
+                    )
+                };
+                match compare(field, x, y) {
+                    Ordering::Equal => continue,
+                    other => return other,
+                }
+            }
+            Ordering::Equal
+        }
+        _ => match (a, b) {
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            _ => a.to_string().cmp(&b.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_str(michelson: &str, schema: &str) -> Result<Value> {
+        decode(
+            &Micheline::parse_michelson(michelson).unwrap(),
+            &Micheline::parse_michelson(schema).unwrap(),
+        )
+    }
+
+    fn encode_str(data: Value, schema: &str) -> Result<String> {
+        encode(&data, &Micheline::parse_michelson(schema).unwrap()).map(|m| m.to_michelson())
+    }
+
+    #[test]
+    fn test_annot() {
+        let field = Micheline::parse_michelson("int %foo").unwrap();
+        assert_eq!(annot(&field, 3), "foo");
+        let field = Micheline::parse_michelson("int :_Liq_entry_bar").unwrap();
+        assert_eq!(annot(&field, 3), "bar");
+        let field = Micheline::parse_michelson("int").unwrap();
+        assert_eq!(annot(&field, 3), "3");
+    }
+
+    #[test]
+    fn test_decode_record_positions() {
+        let decoded = decode_str(
+            r#"Pair 1 (Pair "a" (Pair 2 3)) 4"#,
+            "pair int (pair string (pair %p int int)) nat",
+        )
+        .unwrap();
+        let expected = json!({ "0": "1", "1": "a", "p": { "2": "2", "3": "3" }, "3": "4" });
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_decode_comb() {
+        let decoded = decode_str("{ 1 ; 2 ; 3 }", "pair (int %a) (int %b) (int %c)").unwrap();
+        assert_eq!(decoded, json!({ "a": "1", "b": "2", "c": "3" }));
+    }
+
+    #[test]
+    fn test_decode_or_positions() {
+        let schema = "or (or (pair int nat) string) (pair int nat)";
+        let decoded = decode_str("Right (Pair 1 2)", schema).unwrap();
+        assert_eq!(decoded, json!({ "2": { "2": "1", "3": "2" } }));
+        let decoded = decode_str(r#"Left (Right "a")"#, schema).unwrap();
+        assert_eq!(decoded, json!({ "1": "a" }));
+    }
+
+    #[test]
+    fn test_decode_domain_types() {
+        let decoded = decode_str(
+            "Pair 0x00000000000000000000000000000000000000000000 0x7a06a770 \"2022-08-01T14:00:00+02:00\" 0",
+            "pair (address %a) (chain_id %c) (timestamp %t) (timestamp %u)",
+        )
+        .unwrap();
+        let expected = json!({
+            "a": "tz1Ke2h7sDdakHJQh8WX4Z372du1KChsksyU",
+            "c": "NetXdQprcVkpaWU",
+            "t": "2022-08-01T12:00:00.000Z",
+            "u": "1970-01-01T00:00:00.000Z",
+        });
+        assert_eq!(decoded, expected);
+        let decoded = decode_str(r#""2022-08-01T12:00:00.25Z""#, "timestamp").unwrap();
+        assert_eq!(decoded, json!("2022-08-01T12:00:00.250Z"));
+    }
+
+    #[test]
+    fn test_decode_big_map_and_ticket() {
+        assert_eq!(decode_str("42", "big_map int int").unwrap(), json!("42"));
+        let decoded = decode_str(
+            r#"Pair "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi" "foo" 3"#,
+            "ticket string",
+        )
+        .unwrap();
+        let expected = json!({
+            "ticketer": "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi",
+            "value": "foo",
+            "amount": "3",
+        });
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(decode_str("1", "string").is_err());
+        assert!(decode_str("Pair 1", "pair int int").is_err());
+        assert!(decode_str("Some 1", "or int int").is_err());
+        assert!(decode_str("0x0102", "address").is_err());
+        assert!(decode_str("1", "foo").is_err());
+        assert!(decode_str(r#""yesterday""#, "timestamp").is_err());
+        assert!(decode_str("1", "unit").is_err());
+        assert!(decode_str("Pair Unit Unit", "unit").is_err());
+        assert!(decode_str("{}", "unit").is_err());
+    }
+
+    #[test]
+    fn test_encode_record_positions() {
+        let data = json!({ "0": "1", "1": "a", "p": { "2": "2", "3": "3" }, "3": 4 });
+        let encoded = encode_str(data, "pair int (pair string (pair %p int int)) nat").unwrap();
+        assert_eq!(encoded, r#"Pair 1 (Pair (Pair "a" (Pair 2 3)) 4)"#);
+    }
+
+    #[test]
+    fn test_encode_or() {
+        let schema = "or (or (pair int nat) string) (pair int nat)";
+        let encoded = encode_str(json!({ "1": "a" }), schema).unwrap();
+        assert_eq!(encoded, r#"Left (Right "a")"#);
+        let schema = "or (or (unit %first) (unit %second)) (int %third)";
+        let encoded = encode_str(json!({ "__enum__": "Second" }), schema).unwrap();
+        assert_eq!(encoded, "Left (Right Unit)");
+        assert!(encode_str(json!({ "fourth": "1" }), schema).is_err());
+    }
+
+    #[test]
+    fn test_encode_sorted_by_michelson_order() {
+        // Rollup addresses: `txr1` has a lower tag than `sr1`
+        let sr1 = encoding::b58check_encode(encoding::PREFIX_SR1, &[0; 20]);
+        let txr1 = encoding::b58check_encode(encoding::PREFIX_TXR1, &[0; 20]);
+        let encoded = encode_str(json!([sr1, txr1]), "set address").unwrap();
+        assert_eq!(encoded, format!(r#"{{ "{}" ; "{}" }}"#, txr1, sr1));
+
+        let schema = "set (or (int %a) (or (string %b) (nat %c)))";
+        let data = json!([{ "c": "1" }, { "b": "z" }, { "a": "5" }, { "b": "y" }, { "a": "-1" }]);
+        let encoded = encode_str(data, schema).unwrap();
+        assert_eq!(
+            encoded,
+            r#"{ Left -1 ; Left 5 ; Right (Left "y") ; Right (Left "z") ; Right (Right 1) }"#
+        );
+
+        let data = json!(["2022-08-01T12:00:00.5Z", 1659355200, "1970-01-01T00:00:01Z"]);
+        let encoded = encode_str(data, "set timestamp").unwrap();
+        assert_eq!(
+            encoded,
+            r#"{ "1970-01-01T00:00:01Z" ; 1659355200 ; "2022-08-01T12:00:00.5Z" }"#
+        );
+    }
+
+    #[test]
+    fn test_encode_sorted() {
+        let encoded = encode_str(json!(["10", "9", "-1"]), "set int").unwrap();
+        assert_eq!(encoded, "{ -1 ; 9 ; 10 }");
+        let data = json!({ "MichelsonMap": {
+            "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi": "a",
+            "tz1Ke2h7sDdakHJQh8WX4Z372du1KChsksyU": "b",
+        } });
+        let encoded = encode_str(data, "map address string").unwrap();
+        assert_eq!(
+            encoded,
+            r#"{ Elt "tz1Ke2h7sDdakHJQh8WX4Z372du1KChsksyU" "b" ; Elt "KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi" "a" }"#
+        );
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        assert!(encode_str(json!("-1"), "nat").is_err());
+        assert!(encode_str(json!("1.5"), "int").is_err());
+        assert!(encode_str(json!("abc"), "bytes").is_err());
+        assert!(encode_str(json!("tz1burn"), "address").is_err());
+        assert!(encode_str(json!({ "a": "1" }), "pair (int %a) (int %b)").is_err());
+        assert!(encode_str(json!("1"), "foo").is_err());
+        let duplicate = json!({ "MichelsonMap": { "01": "a", "1": "b" } });
+        assert!(encode_str(duplicate, "map nat string").is_err());
+        assert!(encode_str(json!(["1", 1]), "set int").is_err());
+        assert_eq!(
+            encode_str(json!(["1", "2"]), "set int").unwrap(),
+            "{ 1 ; 2 }"
+        );
+    }
+}
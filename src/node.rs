@@ -0,0 +1,438 @@
+//! The NodeJS process behind [`Parser`], on Tokio
+use crate::install;
+use crate::michelson::{
+    MichelsonV1Expression, ParserBuilder, Request, RequestContent, Response, ResponseContent,
+    SchemaHandle,
+};
+use crate::pack::encode_hex;
+use crate::{Error, Result};
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+/// Install the necessary Javascript code in the current directory, see [`install_parser_in`]
+///
+/// This step is needed before creating a [`Parser`] with the default [`ParserBuilder`]
+pub async fn install_parser() -> Result<PathBuf> {
+    install_parser_in(Some(Path::new("."))).await
+}
+
+/// Install the necessary Javascript code in `dir`, or in a private temporary directory,
+/// and return the path of the installed bundle (see [`ParserBuilder::bundle`])
+///
+/// The first line of the installed file is a comment with a hash of the bundle:
+/// if the file is already present, and its contents match that hash, it's left untouched.
+/// Otherwise the bundle is written to a temporary file and renamed,
+/// so processes installing the parser at the same time never see a partial file.
+pub async fn install_parser_in(dir: Option<&Path>) -> Result<PathBuf> {
+    let dir = dir.map(Path::to_owned);
+    tokio::task::spawn_blocking(move || install::install_parser_in(dir.as_deref()))
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+/// The main decoder/encoder backed by a NodeJS process using Taquito
+///
+/// NodeJS must be installed on the system,
+/// in particular the `node` binary must be present in the `PATH`
+///
+/// A `Parser` is a handle that can be cloned and shared between tasks:
+/// requests are tagged with an ID and a background task routes each response
+/// to the caller waiting for it, so many requests can be in flight at the same time.
+/// The NodeJS process is killed when the last handle is dropped,
+/// see [`shutdown`](Parser::shutdown) to stop it gracefully.
+///
+/// ## Examples
+/// See the [crate-level documentation](crate).
+///
+/// ## Panics
+///
+/// [`new`](Parser::new) panics if the child process cannot be started
+/// (see [`try_new`](Parser::try_new)), or if it's called outside of a Tokio runtime
+#[derive(Clone)]
+pub struct Parser {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Requests for the writer task, `None` after [`Parser::shutdown`]
+    requests: StdMutex<Option<mpsc::UnboundedSender<String>>>,
+    pending: Pending,
+    timeout: Option<Duration>,
+    next_id: AtomicUsize,
+    exit: ExitReceiver,
+    kill: StdMutex<Option<oneshot::Sender<()>>>,
+}
+
+impl Drop for Inner {
+    /// Kill the child process, if it's still running
+    fn drop(&mut self) {
+        if let Some(kill) = self.kill.get_mut().unwrap().take() {
+            let _ = kill.send(());
+        }
+    }
+}
+
+/// `None` while the child process is running, then its exit status if it could be obtained
+pub(crate) type ExitReceiver = watch::Receiver<Option<Option<ExitStatus>>>;
+
+/// Callers waiting for a response, by request ID, `None` once the child process closed its `stdout`
+type Pending = Arc<StdMutex<Option<HashMap<usize, oneshot::Sender<ResponseContent>>>>>;
+
+/// Removes the entry of a request from [`Pending`] when the caller stops waiting,
+/// including when the future of the request is dropped
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: usize,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(pending) = &mut *self.pending.lock().unwrap() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+impl Parser {
+    /// Create a new `Parser` instance, **this starts a new NodeJS process**
+    ///
+    /// The default settings of [`ParserBuilder`] are used.
+    pub fn new() -> Parser {
+        Parser::try_new().expect("parser::command failed")
+    }
+
+    /// Like [`new`](Parser::new), but returns an error if the child process cannot be started
+    pub fn try_new() -> Result<Parser> {
+        ParserBuilder::new().build()
+    }
+
+    /// Start the parser process of `command`, whose `stdin` and `stdout` are piped
+    fn spawn(mut command: Command, timeout: Option<Duration>) -> Result<Parser> {
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("couldn't get stdin");
+        let stdout = child.stdout.take().expect("couldn't get stdout");
+        let pending = Arc::new(StdMutex::new(Some(HashMap::new())));
+        tokio::spawn(route_responses(stdout, pending.clone()));
+        let (requests, lines) = mpsc::unbounded_channel();
+        tokio::spawn(write_requests(stdin, lines));
+        let (exit_sender, exit) = watch::channel(None);
+        let (kill, killed) = oneshot::channel();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = killed => {
+                    // Fails if the process already exited
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            let _ = exit_sender.send(Some(status.ok()));
+        });
+        Ok(Parser {
+            inner: Arc::new(Inner {
+                requests: StdMutex::new(Some(requests)),
+                pending,
+                timeout,
+                next_id: AtomicUsize::new(0),
+                exit,
+                kill: StdMutex::new(Some(kill)),
+            }),
+        })
+    }
+
+    /// Wait until the child process exits, and return its exit status if it can be obtained
+    pub async fn exited(&self) -> Option<ExitStatus> {
+        wait_exit(self.exit_receiver()).await
+    }
+
+    /// Stop the child process and return its exit status
+    ///
+    /// `stdin` is closed once the requests already sent are written,
+    /// which makes the process exit after it handled them.
+    /// If it's still running after `timeout`, it's killed.
+    /// This affects all the clones of this `Parser`, further requests fail.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<ExitStatus> {
+        self.inner.requests.lock().unwrap().take();
+        let exit = self.exit_receiver();
+        let status = match tokio::time::timeout(timeout, wait_exit(exit.clone())).await {
+            Ok(status) => status,
+            Err(_) => {
+                if let Some(kill) = self.inner.kill.lock().unwrap().take() {
+                    let _ = kill.send(());
+                }
+                wait_exit(exit).await
+            }
+        };
+        status.ok_or_else(|| {
+            Error::IoError(std::io::Error::other(
+                "the exit status of the parser is not available",
+            ))
+        })
+    }
+
+    pub(crate) fn exit_receiver(&self) -> ExitReceiver {
+        self.inner.exit.clone()
+    }
+
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        self.encode_with_timeout(data, schema, self.inner.timeout)
+            .await
+    }
+
+    /// Like [`encode`](Parser::encode), with a timeout for this call instead of
+    /// the [default one](ParserBuilder::timeout)
+    pub async fn encode_with_timeout(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+        timeout: Option<Duration>,
+    ) -> Result<MichelsonV1Expression> {
+        let content = RequestContent::Encode { data, schema };
+        self.request(content, timeout).await?.encoded()
+    }
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        self.decode_with_timeout(michelson, schema, self.inner.timeout)
+            .await
+    }
+
+    /// Like [`decode`](Parser::decode), with a timeout for this call instead of
+    /// the [default one](ParserBuilder::timeout)
+    pub async fn decode_with_timeout(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+        timeout: Option<Duration>,
+    ) -> Result<Value> {
+        let content = RequestContent::Decode { michelson, schema };
+        self.request(content, timeout).await?.decoded()
+    }
+
+    /// Encode several values with the same schema, in a single request
+    ///
+    /// Each value has its own result, the whole batch fails only if the request itself fails.
+    pub async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        let content = RequestContent::EncodeBatch { data, schema };
+        self.request(content, self.inner.timeout)
+            .await?
+            .encoded_batch()
+    }
+
+    /// Decode several values with the same schema, in a single request
+    ///
+    /// Each value has its own result, the whole batch fails only if the request itself fails.
+    pub async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        let content = RequestContent::DecodeBatch { michelson, schema };
+        self.request(content, self.inner.timeout)
+            .await?
+            .decoded_batch()
+    }
+
+    /// Register `schema` in the parser process, to convert values by its handle
+    ///
+    /// The schema is sent and parsed only once, instead of once per value.
+    /// The handle is valid until it's [unregistered](Parser::unregister_schema),
+    /// or the process exits.
+    pub async fn register_schema(&self, schema: MichelsonV1Expression) -> Result<SchemaHandle> {
+        let content = RequestContent::RegisterSchema { schema };
+        self.request(content, self.inner.timeout)
+            .await?
+            .registered()
+    }
+
+    /// Forget a registered schema, returns `false` if it wasn't registered
+    pub async fn unregister_schema(&self, handle: SchemaHandle) -> Result<bool> {
+        let content = RequestContent::UnregisterSchema { handle };
+        self.request(content, self.inner.timeout)
+            .await?
+            .unregistered()
+    }
+
+    /// Like [`encode`](Parser::encode), with a [registered](Parser::register_schema) schema
+    ///
+    /// Returns [`Error::UnknownSchema`] if the schema isn't registered.
+    pub async fn encode_registered(
+        &self,
+        data: Value,
+        handle: SchemaHandle,
+    ) -> Result<MichelsonV1Expression> {
+        let content = RequestContent::EncodeRegistered { data, handle };
+        self.request(content, self.inner.timeout).await?.encoded()
+    }
+
+    /// Like [`decode`](Parser::decode), with a [registered](Parser::register_schema) schema
+    ///
+    /// Returns [`Error::UnknownSchema`] if the schema isn't registered.
+    pub async fn decode_registered(
+        &self,
+        michelson: MichelsonV1Expression,
+        handle: SchemaHandle,
+    ) -> Result<Value> {
+        let content = RequestContent::DecodeRegistered { michelson, handle };
+        self.request(content, self.inner.timeout).await?.decoded()
+    }
+
+    /// Serialise `data`, a Michelson value of type `schema`, like the `PACK` instruction
+    ///
+    /// The bytes start with the `0x05` prefix, they are what `CHECK_SIGNATURE` verifies
+    /// signatures of packed data against.
+    pub async fn pack(
+        &self,
+        data: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<u8>> {
+        let content = RequestContent::Pack { data, schema };
+        self.request(content, self.inner.timeout).await?.packed()
+    }
+
+    /// Deserialise packed `bytes` (with the `0x05` prefix) to a Michelson value of type `schema`,
+    /// like the `UNPACK` instruction
+    pub async fn unpack(
+        &self,
+        bytes: &[u8],
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let bytes = encode_hex(bytes);
+        let content = RequestContent::Unpack { bytes, schema };
+        self.request(content, self.inner.timeout).await?.decoded()
+    }
+
+    /// The default timeout of the requests
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.inner.timeout
+    }
+
+    /// Send a request and wait for its response
+    ///
+    /// Returns [`Error::ReadNone`] if the child process exited before responding,
+    /// and [`Error::Timeout`] if there was no response within `timeout`.
+    /// The request is written by a separate task, so dropping the returned future at any point
+    /// leaves the parser in a consistent state: a late response is discarded.
+    pub(crate) async fn request(
+        &self,
+        content: RequestContent,
+        timeout: Option<Duration>,
+    ) -> Result<ResponseContent> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let line = serde_json::to_string(&Request { id, content })?;
+        let requests = self.inner.requests.lock().unwrap().clone();
+        let requests = requests.ok_or_else(|| {
+            Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "the parser was shut down",
+            ))
+        })?;
+        let (sender, response) = oneshot::channel();
+        match &mut *self.inner.pending.lock().unwrap() {
+            Some(pending) => pending.insert(id, sender),
+            None => return Err(Error::ReadNone),
+        };
+        let _guard = PendingGuard {
+            pending: &self.inner.pending,
+            id,
+        };
+        // Fails if the writer task stopped, the child process exited
+        requests.send(line).map_err(|_| Error::ReadNone)?;
+        drop(requests);
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, response)
+                .await
+                .map_err(|_| Error::Timeout)?,
+            None => response.await,
+        };
+        response.map_err(|_| Error::ReadNone)
+    }
+}
+
+impl ParserBuilder {
+    /// Start the NodeJS process
+    ///
+    /// Must be called from within a Tokio runtime.
+    /// Fails with [`Error::IoError`] if the bundle is missing or the process cannot be started.
+    pub fn build(&self) -> Result<Parser> {
+        let mut command = Command::from(self.command()?);
+        // In case the runtime stops before the process is reaped
+        command.kill_on_drop(true);
+        Parser::spawn(command, self.timeout)
+    }
+}
+
+/// Wait until the child process behind `exit` exits
+pub(crate) async fn wait_exit(mut exit: ExitReceiver) -> Option<ExitStatus> {
+    loop {
+        if let Some(status) = *exit.borrow() {
+            return status;
+        }
+        if exit.changed().await.is_err() {
+            // The waiting task is gone, without a status
+            return None;
+        }
+    }
+}
+
+/// Write the requests to `stdin`, one per line
+///
+/// Stops when all the senders are dropped, or when the child process closed its `stdin`:
+/// the callers waiting for a response are then notified by [`route_responses`].
+async fn write_requests(mut stdin: ChildStdin, mut lines: mpsc::UnboundedReceiver<String>) {
+    while let Some(mut line) = lines.recv().await {
+        line.push('\n');
+        if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Read responses until the child process closes its `stdout`,
+/// and pass each of them to the caller waiting for it
+///
+/// The remaining callers are then notified by dropping their channels.
+async fn route_responses(stdout: ChildStdout, pending: Pending) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response: Response = match serde_json::from_str(&line) {
+            Ok(response) => response,
+            // Not a response, e.g. a stray log line
+            Err(_) => continue,
+        };
+        let waiting = match &mut *pending.lock().unwrap() {
+            Some(pending) => pending.remove(&response.id),
+            None => None,
+        };
+        if let Some(sender) = waiting {
+            // The caller may have given up on the response
+            let _ = sender.send(response.content);
+        }
+    }
+    pending.lock().unwrap().take();
+}
@@ -0,0 +1,834 @@
+//! Binary encoding of [`Micheline`] values, as produced by Michelson's `PACK` and consumed by `UNPACK`
+//!
+//! The format is the one used by the Tezos protocol:
+//!
+//! - packed data starts with the `0x05` prefix
+//! - every node starts with a one-byte tag, followed by its contents
+//! - numbers are encoded as _zarith_ (variable-length, little-endian groups of 7 bits,
+//!   the first byte carries the sign and 6 bits only)
+//! - strings, bytes, sequences and annotations are prefixed by their length on 4 bytes (big-endian)
+//! - primitives are encoded by their index in the protocol's primitive table
+//!
+//! Note that [`Micheline::pack`] is _untyped_: values are packed exactly as they appear in the `Micheline` tree.
+//! The protocol packs some types (e.g. `address`, `key_hash`, `timestamp`) in their optimised binary form,
+//! [`Micheline::pack_typed`] converts such values first to get the same bytes as the `PACK` instruction.
+use crate::encoding;
+use crate::micheline::Micheline;
+
+use num_bigint::{BigInt, BigUint, Sign};
+
+use std::fmt;
+
+/// The prefix of packed Michelson data
+pub const PACK_PREFIX: u8 = 0x05;
+
+/// Maximum nesting depth accepted by [`Micheline::unpack`]
+pub const MAX_DEPTH: usize = 256;
+
+/// Maximum length, in bytes, of a number accepted by [`Micheline::unpack`]
+pub const MAX_NUMBER_BYTES: usize = 4096;
+
+const TAG_INT: u8 = 0x00;
+const TAG_STRING: u8 = 0x01;
+const TAG_SEQ: u8 = 0x02;
+const TAG_PRIM_0: u8 = 0x03;
+const TAG_PRIM_0_ANNOTS: u8 = 0x04;
+const TAG_PRIM_1: u8 = 0x05;
+const TAG_PRIM_1_ANNOTS: u8 = 0x06;
+const TAG_PRIM_2: u8 = 0x07;
+const TAG_PRIM_2_ANNOTS: u8 = 0x08;
+const TAG_PRIM_N: u8 = 0x09;
+const TAG_BYTES: u8 = 0x0a;
+
+/// Michelson primitives, indexed by their binary code
+pub const PRIMITIVES: [&str; 157] = [
+    "parameter",
+    "storage",
+    "code",
+    "False",
+    "Elt",
+    "Left",
+    "None",
+    "Pair",
+    "Right",
+    "Some",
+    "True",
+    "Unit",
+    "PACK",
+    "UNPACK",
+    "BLAKE2B",
+    "SHA256",
+    "SHA512",
+    "ABS",
+    "ADD",
+    "AMOUNT",
+    "AND",
+    "BALANCE",
+    "CAR",
+    "CDR",
+    "CHECK_SIGNATURE",
+    "COMPARE",
+    "CONCAT",
+    "CONS",
+    "CREATE_ACCOUNT",
+    "CREATE_CONTRACT",
+    "IMPLICIT_ACCOUNT",
+    "DIP",
+    "DROP",
+    "DUP",
+    "EDIV",
+    "EMPTY_MAP",
+    "EMPTY_SET",
+    "EQ",
+    "EXEC",
+    "FAILWITH",
+    "GE",
+    "GET",
+    "GT",
+    "HASH_KEY",
+    "IF",
+    "IF_CONS",
+    "IF_LEFT",
+    "IF_NONE",
+    "INT",
+    "LAMBDA",
+    "LE",
+    "LEFT",
+    "LOOP",
+    "LSL",
+    "LSR",
+    "LT",
+    "MAP",
+    "MEM",
+    "MUL",
+    "NEG",
+    "NEQ",
+    "NIL",
+    "NONE",
+    "NOT",
+    "NOW",
+    "OR",
+    "PAIR",
+    "PUSH",
+    "RIGHT",
+    "SIZE",
+    "SOME",
+    "SOURCE",
+    "SENDER",
+    "SELF",
+    "STEPS_TO_QUOTA",
+    "SUB",
+    "SWAP",
+    "TRANSFER_TOKENS",
+    "SET_DELEGATE",
+    "UNIT",
+    "UPDATE",
+    "XOR",
+    "ITER",
+    "LOOP_LEFT",
+    "ADDRESS",
+    "CONTRACT",
+    "ISNAT",
+    "CAST",
+    "RENAME",
+    "bool",
+    "contract",
+    "int",
+    "key",
+    "key_hash",
+    "lambda",
+    "list",
+    "map",
+    "big_map",
+    "nat",
+    "option",
+    "or",
+    "pair",
+    "set",
+    "signature",
+    "string",
+    "bytes",
+    "mutez",
+    "timestamp",
+    "unit",
+    "operation",
+    "address",
+    "SLICE",
+    "DIG",
+    "DUG",
+    "EMPTY_BIG_MAP",
+    "APPLY",
+    "chain_id",
+    "CHAIN_ID",
+    "LEVEL",
+    "SELF_ADDRESS",
+    "never",
+    "NEVER",
+    "UNPAIR",
+    "VOTING_POWER",
+    "TOTAL_VOTING_POWER",
+    "KECCAK",
+    "SHA3",
+    "PAIRING_CHECK",
+    "bls12_381_g1",
+    "bls12_381_g2",
+    "bls12_381_fr",
+    "sapling_state",
+    "sapling_transaction_deprecated",
+    "SAPLING_EMPTY_STATE",
+    "SAPLING_VERIFY_UPDATE",
+    "ticket",
+    "TICKET_DEPRECATED",
+    "READ_TICKET",
+    "SPLIT_TICKET",
+    "JOIN_TICKETS",
+    "GET_AND_UPDATE",
+    "chest",
+    "chest_key",
+    "OPEN_CHEST",
+    "VIEW",
+    "view",
+    "constant",
+    "SUB_MUTEZ",
+    "tx_rollup_l2_address",
+    "MIN_BLOCK_TIME",
+    "sapling_transaction",
+    "EMIT",
+    "Lambda_rec",
+    "LAMBDA_REC",
+    "TICKET",
+    "BYTES",
+    "NAT",
+];
+
+/// Errors of the binary encoding and decoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// The input doesn't start with the `0x05` prefix
+    MissingPrefix,
+    /// The input ended in the middle of a value
+    UnexpectedEof,
+    /// Unknown node tag
+    UnknownTag(u8),
+    /// Unknown binary primitive code
+    UnknownPrimitiveCode(u8),
+    /// Primitive not present in [`PRIMITIVES`]
+    UnknownPrimitive(String),
+    /// A string or an annotation is not valid UTF-8
+    InvalidUtf8,
+    /// A number is not a valid decimal integer
+    InvalidNumber(String),
+    /// A zarith number with trailing zero bits, or a negative zero
+    NonCanonicalNumber,
+    /// A zarith number longer than [`MAX_NUMBER_BYTES`]
+    NumberTooLong,
+    /// A `bytes` value is not a valid hex string
+    InvalidBytes(String),
+    /// A string, bytes or sequence doesn't fit in the 4-byte length prefix
+    TooLong(usize),
+    /// The value is nested deeper than [`MAX_DEPTH`]
+    TooDeep,
+    /// Extra bytes after the end of the value
+    TrailingBytes(usize),
+    /// A value that doesn't match its type in [`Micheline::pack_typed`]
+    TypeMismatch(String),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for PackError {}
+
+impl Micheline {
+    /// Serialise as packed data, like the `PACK` instruction (including the `0x05` prefix)
+    pub fn pack(&self) -> Result<Vec<u8>, PackError> {
+        let mut out = vec![PACK_PREFIX];
+        self.encode_binary(&mut out)?;
+        Ok(out)
+    }
+
+    /// Serialise as packed data of type `typ`, like the `PACK` instruction
+    ///
+    /// Domain-specific values are packed in their optimised form (e.g. `address`es as bytes,
+    /// `timestamp`s as numbers) and pairs as nested binary pairs, as the protocol does.
+    pub fn pack_typed(&self, typ: &Micheline) -> Result<Vec<u8>, PackError> {
+        optimise(self, typ, 0)?.pack()
+    }
+
+    /// Deserialise packed data, like the `UNPACK` instruction (expects the `0x05` prefix)
+    pub fn unpack(bytes: &[u8]) -> Result<Micheline, PackError> {
+        match bytes.split_first() {
+            Some((&PACK_PREFIX, rest)) => Micheline::decode_binary(rest),
+            Some(_) => Err(PackError::MissingPrefix),
+            None => Err(PackError::UnexpectedEof),
+        }
+    }
+
+    /// Serialise in the binary format, without the `0x05` prefix
+    pub fn encode_binary(&self, out: &mut Vec<u8>) -> Result<(), PackError> {
+        match self {
+            Micheline::Number { int } => {
+                out.push(TAG_INT);
+                write_zarith(&parse_number(int)?, out);
+            }
+            Micheline::String { string } => {
+                out.push(TAG_STRING);
+                write_bytes(string.as_bytes(), out)?;
+            }
+            Micheline::Bytes { bytes } => {
+                out.push(TAG_BYTES);
+                write_bytes(&decode_hex(bytes)?, out)?;
+            }
+            Micheline::Seq(items) => {
+                out.push(TAG_SEQ);
+                let mut inner = vec![];
+                for item in items {
+                    item.encode_binary(&mut inner)?;
+                }
+                write_bytes(&inner, out)?;
+            }
+            Micheline::Prim { prim, args, annots } => {
+                let code = primitive_code(prim)?;
+                let has_annots = !annots.is_empty();
+                match (args.len(), has_annots) {
+                    (0, false) => out.push(TAG_PRIM_0),
+                    (0, true) => out.push(TAG_PRIM_0_ANNOTS),
+                    (1, false) => out.push(TAG_PRIM_1),
+                    (1, true) => out.push(TAG_PRIM_1_ANNOTS),
+                    (2, false) => out.push(TAG_PRIM_2),
+                    (2, true) => out.push(TAG_PRIM_2_ANNOTS),
+                    _ => out.push(TAG_PRIM_N),
+                }
+                out.push(code);
+                if args.len() <= 2 {
+                    for arg in args {
+                        arg.encode_binary(out)?;
+                    }
+                    if has_annots {
+                        write_bytes(annots.join(" ").as_bytes(), out)?;
+                    }
+                } else {
+                    let mut inner = vec![];
+                    for arg in args {
+                        arg.encode_binary(&mut inner)?;
+                    }
+                    write_bytes(&inner, out)?;
+                    // generic primitives always carry the annotation field
+                    write_bytes(annots.join(" ").as_bytes(), out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserialise a single value in the binary format, without the `0x05` prefix
+    ///
+    /// Fails if there are bytes left after the value.
+    pub fn decode_binary(bytes: &[u8]) -> Result<Micheline, PackError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let value = reader.read_node(0)?;
+        if reader.pos < bytes.len() {
+            return Err(PackError::TrailingBytes(bytes.len() - reader.pos));
+        }
+        Ok(value)
+    }
+}
+
+/// Binary code of a primitive
+pub fn primitive_code(prim: &str) -> Result<u8, PackError> {
+    PRIMITIVES
+        .iter()
+        .position(|p| *p == prim)
+        .map(|i| i as u8)
+        .ok_or_else(|| PackError::UnknownPrimitive(prim.to_owned()))
+}
+
+/// Primitive of a binary code
+pub fn primitive_name(code: u8) -> Result<&'static str, PackError> {
+    PRIMITIVES
+        .get(code as usize)
+        .copied()
+        .ok_or(PackError::UnknownPrimitiveCode(code))
+}
+
+/// The optimised form of `value`, of type `typ`, as packed by the protocol
+fn optimise(value: &Micheline, typ: &Micheline, depth: usize) -> Result<Micheline, PackError> {
+    if depth > MAX_DEPTH {
+        return Err(PackError::TooDeep);
+    }
+    let (prim, args) = match typ {
+        Micheline::Prim { prim, args, .. } => (prim.as_str(), args.as_slice()),
+        _ => return Err(PackError::TypeMismatch(format!("invalid type {:?}", typ))),
+    };
+    let mismatch = || PackError::TypeMismatch(format!("{:?} is not a `{}`", value, prim));
+    let readable = |decode: fn(&str) -> Option<Vec<u8>>| match value {
+        Micheline::String { string } => decode(string)
+            .map(|bytes| Micheline::Bytes {
+                bytes: encode_hex(&bytes),
+            })
+            .ok_or_else(mismatch),
+        Micheline::Bytes { .. } => Ok(value.clone()),
+        _ => Err(mismatch()),
+    };
+    let optimised = match (prim, args, value) {
+        ("address" | "contract", _, _) => readable(encoding::decode_address)?,
+        ("key_hash", _, _) => readable(encoding::decode_key_hash)?,
+        ("key", _, _) => readable(encoding::decode_key)?,
+        ("signature", _, _) => readable(encoding::decode_signature)?,
+        ("chain_id", _, _) => readable(encoding::decode_chain_id)?,
+        ("timestamp", _, Micheline::String { string }) => Micheline::Number {
+            int: encoding::parse_timestamp(string)
+                .ok_or_else(mismatch)?
+                .to_string(),
+        },
+        ("pair", [first, rest @ ..], _) if !rest.is_empty() => {
+            let (left, right) = split_pair(value).ok_or_else(mismatch)?;
+            let right_type = match rest {
+                [second] => second.clone(),
+                _ => Micheline::Prim {
+                    prim: "pair".to_owned(),
+                    args: rest.to_vec(),
+                    annots: vec![],
+                },
+            };
+            prim_value(
+                "Pair",
+                vec![
+                    optimise(&left, first, depth + 1)?,
+                    optimise(&right, &right_type, depth + 1)?,
+                ],
+            )
+        }
+        ("or", [left, right], Micheline::Prim { prim, args, .. }) if args.len() == 1 => {
+            match prim.as_str() {
+                "Left" => prim_value("Left", vec![optimise(&args[0], left, depth + 1)?]),
+                "Right" => prim_value("Right", vec![optimise(&args[0], right, depth + 1)?]),
+                _ => return Err(mismatch()),
+            }
+        }
+        ("option", [inner], Micheline::Prim { prim, args, .. }) if prim == "Some" => {
+            match args.as_slice() {
+                [arg] => prim_value("Some", vec![optimise(arg, inner, depth + 1)?]),
+                _ => return Err(mismatch()),
+            }
+        }
+        ("list" | "set", [inner], Micheline::Seq(items)) => Micheline::Seq(
+            items
+                .iter()
+                .map(|item| optimise(item, inner, depth + 1))
+                .collect::<Result<_, _>>()?,
+        ),
+        ("map" | "big_map", [key, val], Micheline::Seq(items)) => Micheline::Seq(
+            items
+                .iter()
+                .map(|item| match item {
+                    Micheline::Prim { prim, args, .. } if prim == "Elt" && args.len() == 2 => {
+                        Ok(prim_value(
+                            "Elt",
+                            vec![
+                                optimise(&args[0], key, depth + 1)?,
+                                optimise(&args[1], val, depth + 1)?,
+                            ],
+                        ))
+                    }
+                    _ => Err(mismatch()),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => value.clone(),
+    };
+    Ok(optimised)
+}
+
+/// The two sides of a pair value, right combs (`Pair a b c` or `{ a; b; c }`) are nested
+fn split_pair(value: &Micheline) -> Option<(Micheline, Micheline)> {
+    let (items, seq) = match value {
+        Micheline::Prim { prim, args, .. } if prim == "Pair" => (args, false),
+        Micheline::Seq(items) => (items, true),
+        _ => return None,
+    };
+    match items.as_slice() {
+        [left, right] => Some((left.clone(), right.clone())),
+        [left, rest @ ..] if rest.len() > 1 => {
+            let right = if seq {
+                Micheline::Seq(rest.to_vec())
+            } else {
+                prim_value("Pair", rest.to_vec())
+            };
+            Some((left.clone(), right))
+        }
+        _ => None,
+    }
+}
+
+fn prim_value(prim: &str, args: Vec<Micheline>) -> Micheline {
+    Micheline::Prim {
+        prim: prim.to_owned(),
+        args,
+        annots: vec![],
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_u8(&mut self) -> Result<u8, PackError> {
+        let b = *self.bytes.get(self.pos).ok_or(PackError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], PackError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(PackError::UnexpectedEof);
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn read_length_prefixed(&mut self) -> Result<&'a [u8], PackError> {
+        let len = self.read_slice(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        self.read_slice(len)
+    }
+
+    fn read_string(&mut self) -> Result<String, PackError> {
+        let bytes = self.read_length_prefixed()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| PackError::InvalidUtf8)
+    }
+
+    fn read_annots(&mut self) -> Result<Vec<String>, PackError> {
+        let annots = self.read_string()?;
+        Ok(annots
+            .split(' ')
+            .filter(|a| !a.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    fn read_zarith(&mut self) -> Result<BigInt, PackError> {
+        let first = self.read_u8()?;
+        let negative = first & 0x40 != 0;
+        // The 7-bit digits following the first 6 bits, least significant first
+        let mut digits = vec![];
+        let mut more = first & 0x80 != 0;
+        while more {
+            if digits.len() + 1 == MAX_NUMBER_BYTES {
+                return Err(PackError::NumberTooLong);
+            }
+            let b = self.read_u8()?;
+            digits.push(b & 0x7f);
+            more = b & 0x80 != 0;
+        }
+        // The protocol rejects padding bytes and `-0`
+        if digits.last() == Some(&0) {
+            return Err(PackError::NonCanonicalNumber);
+        }
+        let high = BigUint::from_radix_le(&digits, 128).unwrap_or_default();
+        let abs = (high << 6u32) | BigUint::from(first & 0x3f);
+        if negative && abs == BigUint::default() {
+            return Err(PackError::NonCanonicalNumber);
+        }
+        let sign = if negative { Sign::Minus } else { Sign::Plus };
+        Ok(BigInt::from_biguint(sign, abs))
+    }
+
+    fn read_nodes(&mut self, depth: usize) -> Result<Vec<Micheline>, PackError> {
+        let bytes = self.read_length_prefixed()?;
+        let mut inner = Reader { bytes, pos: 0 };
+        let mut nodes = vec![];
+        while inner.pos < bytes.len() {
+            nodes.push(inner.read_node(depth + 1)?);
+        }
+        Ok(nodes)
+    }
+
+    fn read_prim(
+        &mut self,
+        arity: usize,
+        with_annots: bool,
+        depth: usize,
+    ) -> Result<Micheline, PackError> {
+        let prim = primitive_name(self.read_u8()?)?.to_owned();
+        let mut args = Vec::with_capacity(arity);
+        for _ in 0..arity {
+            args.push(self.read_node(depth + 1)?);
+        }
+        let annots = if with_annots {
+            self.read_annots()?
+        } else {
+            vec![]
+        };
+        Ok(Micheline::Prim { prim, args, annots })
+    }
+
+    fn read_node(&mut self, depth: usize) -> Result<Micheline, PackError> {
+        if depth > MAX_DEPTH {
+            return Err(PackError::TooDeep);
+        }
+        match self.read_u8()? {
+            TAG_INT => Ok(Micheline::Number {
+                int: self.read_zarith()?.to_string(),
+            }),
+            TAG_STRING => Ok(Micheline::String {
+                string: self.read_string()?,
+            }),
+            TAG_BYTES => Ok(Micheline::Bytes {
+                bytes: encode_hex(self.read_length_prefixed()?),
+            }),
+            TAG_SEQ => Ok(Micheline::Seq(self.read_nodes(depth)?)),
+            TAG_PRIM_0 => self.read_prim(0, false, depth),
+            TAG_PRIM_0_ANNOTS => self.read_prim(0, true, depth),
+            TAG_PRIM_1 => self.read_prim(1, false, depth),
+            TAG_PRIM_1_ANNOTS => self.read_prim(1, true, depth),
+            TAG_PRIM_2 => self.read_prim(2, false, depth),
+            TAG_PRIM_2_ANNOTS => self.read_prim(2, true, depth),
+            TAG_PRIM_N => {
+                let prim = primitive_name(self.read_u8()?)?.to_owned();
+                let args = self.read_nodes(depth)?;
+                let annots = self.read_annots()?;
+                Ok(Micheline::Prim { prim, args, annots })
+            }
+            tag => Err(PackError::UnknownTag(tag)),
+        }
+    }
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) -> Result<(), PackError> {
+    let len = u32::try_from(bytes.len()).map_err(|_| PackError::TooLong(bytes.len()))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn write_zarith(n: &BigInt, out: &mut Vec<u8>) {
+    let mut abs = n.magnitude().clone();
+    let low_bits = |n: &BigUint, mask: u32| (n.iter_u32_digits().next().unwrap_or(0) & mask) as u8;
+
+    let mut byte = low_bits(&abs, 0x3f);
+    if n.sign() == Sign::Minus {
+        byte |= 0x40;
+    }
+    abs >>= 6;
+    while abs != BigUint::default() {
+        out.push(byte | 0x80);
+        byte = low_bits(&abs, 0x7f);
+        abs >>= 7;
+    }
+    out.push(byte);
+}
+
+pub(crate) fn parse_number(s: &str) -> Result<BigInt, PackError> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(PackError::InvalidNumber(s.to_owned()));
+    }
+    s.parse()
+        .map_err(|_| PackError::InvalidNumber(s.to_owned()))
+}
+
+/// Decode a hex string, the `0x` prefix is optional
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>, PackError> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    // `from_str_radix` alone would accept a sign
+    if hex.len() % 2 == 1 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(PackError::InvalidBytes(s.to_owned()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| PackError::InvalidBytes(s.to_owned()))
+        })
+        .collect()
+}
+
+/// Encode bytes as lower-case hex, without the `0x` prefix (like Taquito does)
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip(json: &str, packed: &str) {
+        let m = Micheline::from_str(json).unwrap();
+        let bytes = m.pack().unwrap();
+        assert_eq!(encode_hex(&bytes), packed);
+        assert_eq!(Micheline::unpack(&bytes).unwrap(), m);
+    }
+
+    #[test]
+    fn test_pack_numbers() {
+        roundtrip(r#"{"int":"0"}"#, "050000");
+        roundtrip(r#"{"int":"1"}"#, "050001");
+        roundtrip(r#"{"int":"-1"}"#, "050041");
+        roundtrip(r#"{"int":"63"}"#, "05003f");
+        roundtrip(r#"{"int":"64"}"#, "05008001");
+        roundtrip(r#"{"int":"-64"}"#, "0500c001");
+        roundtrip(r#"{"int":"1000000"}"#, "050080897a");
+        let big = Micheline::from_str(r#"{"int":"-123456789012345678901234567890"}"#).unwrap();
+        assert_eq!(Micheline::unpack(&big.pack().unwrap()).unwrap(), big);
+    }
+
+    #[test]
+    fn test_pack_strings_and_bytes() {
+        roundtrip(r#"{"string":"foo"}"#, "050100000003666f6f");
+        roundtrip(r#"{"string":""}"#, "050100000000");
+        roundtrip(r#"{"bytes":"deadbeef"}"#, "050a00000004deadbeef");
+    }
+
+    #[test]
+    fn test_pack_prims() {
+        roundtrip(r#"{"prim":"Unit"}"#, "05030b");
+        roundtrip(r#"{"prim":"Some","args":[{"int":"1"}]}"#, "0505090001");
+        roundtrip(
+            r#"{"prim":"Pair","args":[{"int":"1"},{"string":"a"}]}"#,
+            "0507070001010000000161",
+        );
+    }
+
+    #[test]
+    fn test_pack_annotated_and_generic_prims() {
+        roundtrip(r#"{"prim":"int","annots":["%x"]}"#, "05045b000000022578");
+        roundtrip(
+            r#"{"prim":"pair","args":[{"prim":"int","annots":["%x"]},{"prim":"nat","annots":["%y"]}],"annots":[":p"]}"#,
+            "050865045b0000000225780462000000022579000000023a70",
+        );
+        roundtrip(
+            r#"{"prim":"Pair","args":[{"int":"1"},{"int":"2"},{"int":"3"}]}"#,
+            "0509070000000600010002000300000000",
+        );
+    }
+
+    #[test]
+    fn test_pack_sequences() {
+        roundtrip("[]", "050200000000");
+        roundtrip(
+            r#"[{"prim":"Elt","args":[{"string":"a"},{"int":"2"}]}]"#,
+            "05020000000a07040100000001610002",
+        );
+    }
+
+    #[test]
+    fn test_unpack_errors() {
+        assert_eq!(Micheline::unpack(&[]), Err(PackError::UnexpectedEof));
+        assert_eq!(
+            Micheline::unpack(&[0x00, 0x00, 0x00]),
+            Err(PackError::MissingPrefix)
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x01, 0x00, 0x00, 0x00, 0x05, 0x61]),
+            Err(PackError::UnexpectedEof)
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x00, 0x80]),
+            Err(PackError::UnexpectedEof)
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x0b]),
+            Err(PackError::UnknownTag(0x0b))
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x03, 0xff]),
+            Err(PackError::UnknownPrimitiveCode(0xff))
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x00, 0x81, 0x00]),
+            Err(PackError::NonCanonicalNumber)
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x00, 0x40]),
+            Err(PackError::NonCanonicalNumber)
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x00, 0x01, 0x00]),
+            Err(PackError::TrailingBytes(1))
+        );
+        assert_eq!(
+            Micheline::unpack(&[0x05, 0x01, 0x00, 0x00, 0x00, 0x01, 0xff]),
+            Err(PackError::InvalidUtf8)
+        );
+        let mut long = vec![0x05, 0x00];
+        long.extend(vec![0x80; 1_000_000]);
+        assert_eq!(Micheline::unpack(&long), Err(PackError::NumberTooLong));
+        let mut max = vec![0x05, 0x00];
+        max.extend(vec![0x80; MAX_NUMBER_BYTES - 1]);
+        max.push(0x01);
+        assert!(Micheline::unpack(&max).is_ok());
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("0x00ff").unwrap(), vec![0x00, 0xff]);
+        assert!(decode_hex("").unwrap().is_empty());
+        for invalid in ["+f", "-1", "0", "zz", "é0"] {
+            assert_eq!(
+                decode_hex(invalid),
+                Err(PackError::InvalidBytes(invalid.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_unpack_too_deep() {
+        let mut bytes = vec![PACK_PREFIX];
+        for _ in 0..=MAX_DEPTH {
+            bytes.extend_from_slice(&[0x05, 0x09]); // Some ...
+        }
+        bytes.extend_from_slice(&[0x03, 0x0b]);
+        assert_eq!(Micheline::unpack(&bytes), Err(PackError::TooDeep));
+    }
+
+    #[test]
+    fn test_pack_typed() {
+        let packed = |value: &str, typ: &str| {
+            let value = Micheline::from_str(value).unwrap();
+            let typ = Micheline::from_str(typ).unwrap();
+            value.pack_typed(&typ).map(|bytes| encode_hex(&bytes))
+        };
+        assert_eq!(
+            packed(
+                r#"{"string":"tz1bwsEWCwSEXdRvnJxvegQZKeX5dj6oKEys"}"#,
+                r#"{"prim":"address"}"#
+            ),
+            Ok("050a000000160000b2e19a9e74440d86c59f13dab8a18ff873e889ea".to_owned())
+        );
+        assert_eq!(
+            packed(
+                r#"{"string":"1970-01-01T00:01:04Z"}"#,
+                r#"{"prim":"timestamp"}"#
+            ),
+            Ok("05008001".to_owned())
+        );
+        assert_eq!(
+            packed(
+                r#"{"prim":"Some","args":[{"prim":"Pair","args":[{"int":"1"},{"int":"2"},{"int":"3"}]}]}"#,
+                r#"{"prim":"option","args":[{"prim":"pair","args":[{"prim":"int"},{"prim":"int"},{"prim":"int"}]}]}"#
+            ),
+            Ok("05050907070001070700020003".to_owned())
+        );
+        assert!(matches!(
+            packed(r#"{"string":"tz1"}"#, r#"{"prim":"key_hash"}"#),
+            Err(PackError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_pack_errors() {
+        let m = Micheline::from_str(r#"{"int":"1_000"}"#).unwrap();
+        assert_eq!(m.pack(), Err(PackError::InvalidNumber("1_000".to_owned())));
+        let m = Micheline::from_str(r#"{"bytes":"abc"}"#).unwrap();
+        assert_eq!(m.pack(), Err(PackError::InvalidBytes("abc".to_owned())));
+        let m = Micheline::from_str(r#"{"prim":"FOO"}"#).unwrap();
+        assert_eq!(m.pack(), Err(PackError::UnknownPrimitive("FOO".to_owned())));
+    }
+}
@@ -0,0 +1,207 @@
+//! A pool of NodeJS parser processes ([`ParserPool`])
+use crate::codec::Codec;
+use crate::michelson::{MichelsonV1Expression, RequestContent, ResponseContent};
+use crate::{Error, Parser, ParserBuilder, Result};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Job {
+    content: RequestContent,
+    reply: oneshot::Sender<Result<ResponseContent>>,
+}
+
+/// Statistics of a [`ParserPool`], see [`ParserPool::stats`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of parser processes
+    pub workers: usize,
+    /// Number of parser processes handling a request
+    pub busy_workers: usize,
+    /// Number of requests waiting for a free parser
+    pub queued: usize,
+    /// Maximum number of waiting requests
+    pub queue_capacity: usize,
+}
+
+/// A pool of [`Parser`]s, requests are handled by whichever parser is free
+///
+/// The pool is a cheap handle that can be cloned and shared between tasks.
+/// Requests wait in a bounded queue: when it's full, [`encode`](ParserPool::encode)
+/// and [`decode`](ParserPool::decode) wait until there is room for the request.
+/// The parser processes are stopped when the last handle is dropped.
+///
+/// Like [`Parser::new`], this requires the parser to be [installed](crate::install_parser),
+/// see also [`ParserBuilder`].
+#[derive(Clone)]
+pub struct ParserPool {
+    queue: mpsc::Sender<Job>,
+    workers: usize,
+    queue_capacity: usize,
+    busy: Arc<AtomicUsize>,
+}
+
+impl ParserPool {
+    /// Start a pool of `workers` parser processes, with room for `queue_capacity` waiting requests
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// ## Panics
+    ///
+    /// If `workers` or `queue_capacity` is zero, or if a child process cannot be started
+    pub fn new(workers: usize, queue_capacity: usize) -> ParserPool {
+        ParserPool::with_builder(&ParserBuilder::new(), workers, queue_capacity)
+            .expect("parser::command failed")
+    }
+
+    /// Like [`new`](ParserPool::new), with the processes started by `builder`
+    ///
+    /// Returns an error if a process cannot be started, the processes already started are stopped.
+    ///
+    /// ## Panics
+    ///
+    /// If `workers` or `queue_capacity` is zero
+    pub fn with_builder(
+        builder: &ParserBuilder,
+        workers: usize,
+        queue_capacity: usize,
+    ) -> Result<ParserPool> {
+        assert!(workers > 0, "a parser pool needs at least one worker");
+        let parsers = (0..workers)
+            .map(|_| builder.build())
+            .collect::<Result<Vec<Parser>>>()?;
+        let (queue, jobs) = mpsc::channel(queue_capacity);
+        let jobs = Arc::new(Mutex::new(jobs));
+        let busy = Arc::new(AtomicUsize::new(0));
+        for parser in parsers {
+            tokio::spawn(work(parser, jobs.clone(), busy.clone()));
+        }
+        Ok(ParserPool {
+            queue,
+            workers,
+            queue_capacity,
+            busy,
+        })
+    }
+
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        self.submit(RequestContent::Encode { data, schema })
+            .await?
+            .encoded()
+    }
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        self.submit(RequestContent::Decode { michelson, schema })
+            .await?
+            .decoded()
+    }
+
+    /// Encode several values with the same schema, in a single request,
+    /// see [`Parser::encode_batch`]
+    pub async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        self.submit(RequestContent::EncodeBatch { data, schema })
+            .await?
+            .encoded_batch()
+    }
+
+    /// Decode several values with the same schema, in a single request,
+    /// see [`Parser::decode_batch`]
+    pub async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        self.submit(RequestContent::DecodeBatch { michelson, schema })
+            .await?
+            .decoded_batch()
+    }
+
+    /// Current load of the pool
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            workers: self.workers,
+            busy_workers: self.busy.load(Ordering::Relaxed),
+            queued: self.queue_capacity - self.queue.capacity(),
+            queue_capacity: self.queue_capacity,
+        }
+    }
+
+    async fn submit(&self, content: RequestContent) -> Result<ResponseContent> {
+        let (reply, response) = oneshot::channel();
+        self.queue
+            .send(Job { content, reply })
+            .await
+            .map_err(|_| Error::ReadNone)?;
+        // The sender is dropped without a reply if the worker panicked
+        response.await.map_err(|_| Error::ReadNone)?
+    }
+}
+
+async fn work(parser: Parser, jobs: Arc<Mutex<mpsc::Receiver<Job>>>, busy: Arc<AtomicUsize>) {
+    loop {
+        let job = match jobs.lock().await.recv().await {
+            Some(job) => job,
+            None => return,
+        };
+        busy.fetch_add(1, Ordering::Relaxed);
+        let result = parser.request(job.content, parser.timeout()).await;
+        busy.fetch_sub(1, Ordering::Relaxed);
+        // The caller may have given up on the result
+        let _ = job.reply.send(result);
+    }
+}
+
+#[async_trait]
+impl Codec for ParserPool {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        ParserPool::encode(self, data, schema).await
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        ParserPool::decode(self, michelson, schema).await
+    }
+
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        ParserPool::encode_batch(self, data, schema).await
+    }
+
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        ParserPool::decode_batch(self, michelson, schema).await
+    }
+}
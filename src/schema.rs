@@ -0,0 +1,375 @@
+//! Typed representation of schemas (Michelson types), see [`MichelsonType`]
+//!
+//! Schemas are [`Micheline`] values, [`MichelsonType`] checks that they are valid types:
+//! known primitives with the right number of arguments, and no values where types are expected.
+//!
+//! ```
+//! use zfx_michelson::micheline::Micheline;
+//! use zfx_michelson::schema::MichelsonType;
+//!
+//! let schema = Micheline::from_str(r#"{ "prim": "map", "args": [{ "prim": "string" }, { "prim": "int" }] }"#)?;
+//! let typ = MichelsonType::from_micheline(&schema)?;
+//! assert!(matches!(typ, MichelsonType::Map(_, _, _)));
+//! assert_eq!(typ.to_micheline(), schema);
+//! # Ok::<(), zfx_michelson::Error>(())
+//! ```
+use crate::micheline::{Annotations, Micheline};
+use crate::{Error, Result};
+
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+
+/// A Michelson type, with the annotations of each node
+///
+/// (De)serialised like the equivalent [`Micheline`] value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Micheline", into = "Micheline")]
+pub enum MichelsonType {
+    Unit(Annotations),
+    Never(Annotations),
+    Bool(Annotations),
+    Int(Annotations),
+    Nat(Annotations),
+    String(Annotations),
+    ChainId(Annotations),
+    Bytes(Annotations),
+    Mutez(Annotations),
+    KeyHash(Annotations),
+    Key(Annotations),
+    Signature(Annotations),
+    Timestamp(Annotations),
+    Address(Annotations),
+    TxRollupL2Address(Annotations),
+    Operation(Annotations),
+    Bls12381G1(Annotations),
+    Bls12381G2(Annotations),
+    Bls12381Fr(Annotations),
+    Chest(Annotations),
+    ChestKey(Annotations),
+    Option(Box<MichelsonType>, Annotations),
+    List(Box<MichelsonType>, Annotations),
+    Set(Box<MichelsonType>, Annotations),
+    Contract(Box<MichelsonType>, Annotations),
+    Ticket(Box<MichelsonType>, Annotations),
+    /// `ticket_deprecated`, still accepted in legacy contracts
+    TicketDeprecated(Box<MichelsonType>, Annotations),
+    /// A pair, or a right comb of pairs with more than two fields
+    Pair(Vec<MichelsonType>, Annotations),
+    Or(Box<MichelsonType>, Box<MichelsonType>, Annotations),
+    Map(Box<MichelsonType>, Box<MichelsonType>, Annotations),
+    BigMap(Box<MichelsonType>, Box<MichelsonType>, Annotations),
+    /// The type of the argument and of the result
+    Lambda(Box<MichelsonType>, Box<MichelsonType>, Annotations),
+    /// The memo size
+    SaplingState(u16, Annotations),
+    /// The memo size
+    SaplingTransaction(u16, Annotations),
+    /// `sapling_transaction_deprecated`, with the memo size
+    SaplingTransactionDeprecated(u16, Annotations),
+}
+
+type Nullary = fn(Annotations) -> MichelsonType;
+type Unary = fn(Box<MichelsonType>, Annotations) -> MichelsonType;
+type Binary = fn(Box<MichelsonType>, Box<MichelsonType>, Annotations) -> MichelsonType;
+
+const NULLARY: [(&str, Nullary); 21] = [
+    ("unit", MichelsonType::Unit),
+    ("never", MichelsonType::Never),
+    ("bool", MichelsonType::Bool),
+    ("int", MichelsonType::Int),
+    ("nat", MichelsonType::Nat),
+    ("string", MichelsonType::String),
+    ("chain_id", MichelsonType::ChainId),
+    ("bytes", MichelsonType::Bytes),
+    ("mutez", MichelsonType::Mutez),
+    ("key_hash", MichelsonType::KeyHash),
+    ("key", MichelsonType::Key),
+    ("signature", MichelsonType::Signature),
+    ("timestamp", MichelsonType::Timestamp),
+    ("address", MichelsonType::Address),
+    ("tx_rollup_l2_address", MichelsonType::TxRollupL2Address),
+    ("operation", MichelsonType::Operation),
+    ("bls12_381_g1", MichelsonType::Bls12381G1),
+    ("bls12_381_g2", MichelsonType::Bls12381G2),
+    ("bls12_381_fr", MichelsonType::Bls12381Fr),
+    ("chest", MichelsonType::Chest),
+    ("chest_key", MichelsonType::ChestKey),
+];
+
+const UNARY: [(&str, Unary); 6] = [
+    ("option", MichelsonType::Option),
+    ("list", MichelsonType::List),
+    ("set", MichelsonType::Set),
+    ("contract", MichelsonType::Contract),
+    ("ticket", MichelsonType::Ticket),
+    ("ticket_deprecated", MichelsonType::TicketDeprecated),
+];
+
+const BINARY: [(&str, Binary); 4] = [
+    ("or", MichelsonType::Or),
+    ("map", MichelsonType::Map),
+    ("big_map", MichelsonType::BigMap),
+    ("lambda", MichelsonType::Lambda),
+];
+
+fn invalid(message: String) -> Error {
+    Error::InvalidSchema(message)
+}
+
+impl MichelsonType {
+    /// Check and convert a schema
+    ///
+    /// Returns [`Error::InvalidSchema`] for unknown primitives, wrong numbers of arguments,
+    /// and values that are not types.
+    pub fn from_micheline(schema: &Micheline) -> Result<MichelsonType> {
+        let (prim, args, annots) = match schema {
+            Micheline::Prim { prim, args, annots } => (prim.as_str(), args, annots.clone()),
+            other => return Err(invalid(format!("not a type: {:?}", other))),
+        };
+        let arity = |expected: usize| {
+            if args.len() == expected {
+                Ok(())
+            } else {
+                Err(invalid(format!(
+                    "`{}` expects {} argument(s), got {}",
+                    prim,
+                    expected,
+                    args.len()
+                )))
+            }
+        };
+        let arg = |i: usize| MichelsonType::from_micheline(&args[i]).map(Box::new);
+        if let Some((_, typ)) = NULLARY.iter().find(|(name, _)| *name == prim) {
+            arity(0)?;
+            return Ok(typ(annots));
+        }
+        if let Some((_, typ)) = UNARY.iter().find(|(name, _)| *name == prim) {
+            arity(1)?;
+            return Ok(typ(arg(0)?, annots));
+        }
+        if let Some((_, typ)) = BINARY.iter().find(|(name, _)| *name == prim) {
+            arity(2)?;
+            return Ok(typ(arg(0)?, arg(1)?, annots));
+        }
+        match prim {
+            "pair" if args.len() < 2 => Err(invalid(format!(
+                "`pair` expects at least 2 arguments, got {}",
+                args.len()
+            ))),
+            "pair" => {
+                let fields = args
+                    .iter()
+                    .map(MichelsonType::from_micheline)
+                    .collect::<Result<_>>()?;
+                Ok(MichelsonType::Pair(fields, annots))
+            }
+            "sapling_state" | "sapling_transaction" | "sapling_transaction_deprecated" => {
+                arity(1)?;
+                let memo_size = match &args[0] {
+                    Micheline::Number { int } => int.parse().ok(),
+                    _ => None,
+                }
+                .ok_or_else(|| invalid(format!("invalid memo size for `{}`", prim)))?;
+                Ok(match prim {
+                    "sapling_state" => MichelsonType::SaplingState(memo_size, annots),
+                    "sapling_transaction" => MichelsonType::SaplingTransaction(memo_size, annots),
+                    _ => MichelsonType::SaplingTransactionDeprecated(memo_size, annots),
+                })
+            }
+            _ => Err(invalid(format!("unknown type `{}`", prim))),
+        }
+    }
+
+    /// The schema of this type
+    pub fn to_micheline(&self) -> Micheline {
+        use MichelsonType::*;
+
+        let args = match self {
+            Option(t, _)
+            | List(t, _)
+            | Set(t, _)
+            | Contract(t, _)
+            | Ticket(t, _)
+            | TicketDeprecated(t, _) => vec![t.to_micheline()],
+            Or(a, b, _) | Map(a, b, _) | BigMap(a, b, _) | Lambda(a, b, _) => {
+                vec![a.to_micheline(), b.to_micheline()]
+            }
+            Pair(fields, _) => fields.iter().map(MichelsonType::to_micheline).collect(),
+            SaplingState(memo_size, _)
+            | SaplingTransaction(memo_size, _)
+            | SaplingTransactionDeprecated(memo_size, _) => {
+                vec![Micheline::Number {
+                    int: memo_size.to_string(),
+                }]
+            }
+            _ => vec![],
+        };
+        Micheline::Prim {
+            prim: self.prim().to_owned(),
+            args,
+            annots: self.annots().clone(),
+        }
+    }
+
+    /// The Michelson primitive of the type, e.g. `big_map`
+    pub fn prim(&self) -> &'static str {
+        use MichelsonType::*;
+
+        match self {
+            Unit(_) => "unit",
+            Never(_) => "never",
+            Bool(_) => "bool",
+            Int(_) => "int",
+            Nat(_) => "nat",
+            String(_) => "string",
+            ChainId(_) => "chain_id",
+            Bytes(_) => "bytes",
+            Mutez(_) => "mutez",
+            KeyHash(_) => "key_hash",
+            Key(_) => "key",
+            Signature(_) => "signature",
+            Timestamp(_) => "timestamp",
+            Address(_) => "address",
+            TxRollupL2Address(_) => "tx_rollup_l2_address",
+            Operation(_) => "operation",
+            Bls12381G1(_) => "bls12_381_g1",
+            Bls12381G2(_) => "bls12_381_g2",
+            Bls12381Fr(_) => "bls12_381_fr",
+            Chest(_) => "chest",
+            ChestKey(_) => "chest_key",
+            Option(..) => "option",
+            List(..) => "list",
+            Set(..) => "set",
+            Contract(..) => "contract",
+            Ticket(..) => "ticket",
+            TicketDeprecated(..) => "ticket_deprecated",
+            Pair(..) => "pair",
+            Or(..) => "or",
+            Map(..) => "map",
+            BigMap(..) => "big_map",
+            Lambda(..) => "lambda",
+            SaplingState(..) => "sapling_state",
+            SaplingTransaction(..) => "sapling_transaction",
+            SaplingTransactionDeprecated(..) => "sapling_transaction_deprecated",
+        }
+    }
+
+    /// The annotations of the type, e.g. `%field`
+    pub fn annots(&self) -> &Annotations {
+        use MichelsonType::*;
+
+        match self {
+            Unit(a) | Never(a) | Bool(a) | Int(a) | Nat(a) | String(a) | ChainId(a) | Bytes(a)
+            | Mutez(a) | KeyHash(a) | Key(a) | Signature(a) | Timestamp(a) | Address(a)
+            | TxRollupL2Address(a) | Operation(a) | Bls12381G1(a) | Bls12381G2(a)
+            | Bls12381Fr(a) | Chest(a) | ChestKey(a) => a,
+            Option(_, a) | List(_, a) | Set(_, a) | Contract(_, a) | Ticket(_, a) => a,
+            TicketDeprecated(_, a) | Pair(_, a) => a,
+            SaplingState(_, a) | SaplingTransaction(_, a) | SaplingTransactionDeprecated(_, a) => a,
+            Or(_, _, a) | Map(_, _, a) | BigMap(_, _, a) | Lambda(_, _, a) => a,
+        }
+    }
+
+    /// The field annotation of the type, without the `%`
+    pub fn field(&self) -> Option<&str> {
+        self.annots()
+            .iter()
+            .find_map(|annot| annot.strip_prefix('%'))
+    }
+}
+
+impl TryFrom<Micheline> for MichelsonType {
+    type Error = Error;
+
+    fn try_from(schema: Micheline) -> Result<MichelsonType> {
+        MichelsonType::from_micheline(&schema)
+    }
+}
+
+impl TryFrom<&Micheline> for MichelsonType {
+    type Error = Error;
+
+    fn try_from(schema: &Micheline) -> Result<MichelsonType> {
+        MichelsonType::from_micheline(schema)
+    }
+}
+
+impl From<MichelsonType> for Micheline {
+    fn from(typ: MichelsonType) -> Micheline {
+        typ.to_micheline()
+    }
+}
+
+impl From<&MichelsonType> for Micheline {
+    fn from(typ: &MichelsonType) -> Micheline {
+        typ.to_micheline()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(json: &str) -> Result<MichelsonType> {
+        MichelsonType::from_micheline(&Micheline::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_schema_roundtrip() {
+        let schemas = [
+            r#"{"prim":"int","annots":["%a"]}"#,
+            r#"{"prim":"pair","args":[{"prim":"nat","annots":["%n"]},{"prim":"address"},{"prim":"timestamp"}],"annots":[":p"]}"#,
+            r#"{"prim":"or","args":[{"prim":"unit","annots":["%reset"]},{"prim":"option","args":[{"prim":"key_hash"}]}]}"#,
+            r#"{"prim":"big_map","args":[{"prim":"string"},{"prim":"ticket","args":[{"prim":"bytes"}]}]}"#,
+            r#"{"prim":"lambda","args":[{"prim":"list","args":[{"prim":"operation"}]},{"prim":"contract","args":[{"prim":"unit"}]}]}"#,
+            r#"{"prim":"sapling_state","args":[{"int":"8"}]}"#,
+            r#"{"prim":"ticket_deprecated","args":[{"prim":"nat"}],"annots":["%t"]}"#,
+            r#"{"prim":"sapling_transaction_deprecated","args":[{"int":"8"}]}"#,
+        ];
+        for json in schemas {
+            let schema = Micheline::from_str(json).unwrap();
+            let typ = MichelsonType::from_micheline(&schema).unwrap();
+            assert_eq!(typ.to_micheline(), schema);
+            assert_eq!(serde_json::to_string(&typ).unwrap(), json);
+            assert_eq!(serde_json::from_str::<MichelsonType>(json).unwrap(), typ);
+        }
+    }
+
+    #[test]
+    fn test_schema_structure() {
+        let typ =
+            parse(r#"{"prim":"map","args":[{"prim":"string"},{"prim":"int","annots":["%v"]}]}"#)
+                .unwrap();
+        match &typ {
+            MichelsonType::Map(key, value, annots) => {
+                assert_eq!(**key, MichelsonType::String(vec![]));
+                assert_eq!(value.field(), Some("v"));
+                assert!(annots.is_empty());
+            }
+            other => panic!("not a map: {:?}", other),
+        }
+        assert_eq!(typ.prim(), "map");
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let invalid = |json: &str| matches!(parse(json), Err(Error::InvalidSchema(_)));
+        assert!(invalid(r#"{"prim":"foo"}"#));
+        assert!(invalid(r#"{"prim":"Unit"}"#));
+        assert!(invalid(r#"{"prim":"int","args":[{"prim":"int"}]}"#));
+        assert!(invalid(r#"{"prim":"option"}"#));
+        assert!(invalid(r#"{"prim":"map","args":[{"prim":"int"}]}"#));
+        assert!(invalid(r#"{"prim":"pair","args":[{"prim":"int"}]}"#));
+        assert!(invalid(
+            r#"{"prim":"sapling_state","args":[{"string":"8"}]}"#
+        ));
+        assert!(invalid(r#"{"prim":"ticket_deprecated"}"#));
+        assert!(invalid(
+            r#"{"prim":"sapling_transaction_deprecated","args":[{"prim":"nat"}]}"#
+        ));
+        assert!(invalid(r#"{"int":"1"}"#));
+        assert!(invalid(r#"{"prim":"list","args":[{"prim":"lst"}]}"#));
+        assert!(serde_json::from_str::<MichelsonType>(r#"{"prim":"foo"}"#).is_err());
+    }
+}
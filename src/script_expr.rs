@@ -0,0 +1,101 @@
+//! Script-expression hashes (`expr...`) of Michelson values
+//!
+//! Values of `big_map`s are looked up over RPC by the hash of their key
+//! (`/chains/main/blocks/head/context/big_maps/<id>/<hash>`), the Base58Check encoding of
+//! the Blake2b-256 digest of the key packed with its type.
+use crate::encoding::{b58check_encode, PREFIX_EXPR};
+use crate::micheline::Micheline;
+use crate::michelson_types::JsonWrapped;
+use crate::{native, Result};
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use serde::Serialize;
+
+/// The `expr...` hash of `key`, a Michelson value of type `key_type`
+///
+/// ```
+/// use zfx_michelson::micheline::Micheline;
+/// use zfx_michelson::script_expr;
+///
+/// let key = Micheline::from_str(r#"{ "string": "tz1bwsEWCwSEXdRvnJxvegQZKeX5dj6oKEys" }"#)?;
+/// let key_type = Micheline::from_str(r#"{ "prim": "address" }"#)?;
+/// assert!(script_expr::hash(&key, &key_type)?.starts_with("expr"));
+/// # Ok::<(), zfx_michelson::Error>(())
+/// ```
+pub fn hash(key: &Micheline, key_type: &Micheline) -> Result<String> {
+    Ok(hash_packed(&key.pack_typed(key_type)?))
+}
+
+/// The `expr...` hash of a Rust value, encoded according to `schema` like [`native::encode`]
+pub fn hash_wrapped<T>(key: &T, schema: &Micheline) -> Result<String>
+where
+    T: JsonWrapped,
+    T::JsonType: Serialize,
+{
+    let data = serde_json::to_value(key.to_wrapped_json()?)?;
+    hash(&native::encode(&data, schema)?, schema)
+}
+
+/// The `expr...` hash of packed data (including the `0x05` prefix)
+pub fn hash_packed(packed: &[u8]) -> String {
+    b58check_encode(PREFIX_EXPR, &Blake2b::<U32>::digest(packed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pack::decode_hex;
+    use crate::Error;
+
+    fn micheline(json: &str) -> Micheline {
+        Micheline::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_hash_packed() {
+        let packed =
+            decode_hex("050a000000160000b2e19a9e74440d86c59f13dab8a18ff873e889ea").unwrap();
+        assert_eq!(
+            hash_packed(&packed),
+            "exprv6UsC1sN3Fk2XfgcJCL8NCerP5rCGy1PRESZAqr7L2JdzX55EN"
+        );
+    }
+
+    #[test]
+    fn test_hash_typed_keys() {
+        let address = micheline(r#"{"string":"tz1bwsEWCwSEXdRvnJxvegQZKeX5dj6oKEys"}"#);
+        assert_eq!(
+            hash(&address, &micheline(r#"{"prim":"address"}"#)).unwrap(),
+            "exprv6UsC1sN3Fk2XfgcJCL8NCerP5rCGy1PRESZAqr7L2JdzX55EN"
+        );
+
+        let comb = micheline(
+            r#"{"prim":"pair","args":[{"prim":"nat"},{"prim":"nat"},{"prim":"timestamp"}]}"#,
+        );
+        let nested = hash(
+            &micheline(r#"{"prim":"Pair","args":[{"int":"1"},{"prim":"Pair","args":[{"int":"2"},{"int":"0"}]}]}"#),
+            &comb,
+        )
+        .unwrap();
+        let flat = micheline(
+            r#"{"prim":"Pair","args":[{"int":"1"},{"int":"2"},{"string":"1970-01-01T00:00:00Z"}]}"#,
+        );
+        assert_eq!(hash(&flat, &comb).unwrap(), nested);
+        let seq = micheline(r#"[{"int":"1"},{"int":"2"},{"int":"0"}]"#);
+        assert_eq!(hash(&seq, &comb).unwrap(), nested);
+
+        let response = hash(
+            &micheline(r#"{"int":"1"}"#),
+            &micheline(r#"{"prim":"address"}"#),
+        );
+        assert!(matches!(response, Err(Error::PackError(_))));
+    }
+
+    #[test]
+    fn test_hash_wrapped() {
+        let schema = micheline(r#"{"prim":"string"}"#);
+        let expected = hash(&micheline(r#"{"string":"foo"}"#), &schema).unwrap();
+        assert_eq!(hash_wrapped(&"foo".to_owned(), &schema).unwrap(), expected);
+    }
+}
@@ -0,0 +1,358 @@
+//! A [`Parser`] that restarts its NodeJS process when it exits ([`SupervisedParser`])
+use crate::codec::Codec;
+use crate::michelson::{MichelsonV1Expression, RequestContent, ResponseContent};
+use crate::node::wait_exit;
+use crate::{Error, Parser, ParserBuilder, Result};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+
+use std::collections::HashMap;
+use std::io;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Settings of a [`SupervisedParser`]
+#[derive(Clone, Debug)]
+pub struct SupervisorConfig {
+    /// How many times a request is sent again to a new process, after the process handling it exited
+    pub max_retries: usize,
+    /// Delay before the first restart; it doubles after every consecutive restart
+    pub min_backoff: Duration,
+    /// Upper bound of the delay between restarts
+    pub max_backoff: Duration,
+    /// How many consecutive attempts to start a new process fail before giving up;
+    /// the requests waiting for it then fail with the last error
+    pub max_respawns: usize,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        SupervisorConfig {
+            max_retries: 3,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            max_respawns: 10,
+        }
+    }
+}
+
+/// Sent to [subscribers](SupervisedParser::exits) each time the NodeJS process exits
+#[derive(Clone, Debug)]
+pub struct ExitReport {
+    /// The exit status of the process, if it could be obtained
+    pub status: Option<ExitStatus>,
+    /// The requests in flight when the process exited;
+    /// they are retried according to [`SupervisorConfig::max_retries`]
+    pub failed: Vec<RequestContent>,
+    /// Number of times the process exited so far, including this one
+    pub exits: usize,
+}
+
+/// A [`Parser`] that is respawned when its NodeJS process exits
+///
+/// The exits are reported to the [subscribers](SupervisedParser::exits).
+/// Requests interrupted by an exit are sent again to the new process, up to
+/// [`SupervisorConfig::max_retries`] times: encoding and decoding have no side effects.
+///
+/// Like [`Parser`], this is a handle that can be cloned and shared between tasks,
+/// the process is stopped when the last handle is dropped.
+#[derive(Clone)]
+pub struct SupervisedParser {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    builder: ParserBuilder,
+    config: SupervisorConfig,
+    /// The current process and its generation (number of restarts)
+    current: watch::Sender<(Parser, usize)>,
+    /// The last generation whose exit was reported
+    reported: watch::Sender<Option<usize>>,
+    /// The last error of starting a new process, once the supervisor gave up
+    gave_up: watch::Sender<Option<(io::ErrorKind, String)>>,
+    /// Requests in flight, with the generation handling them
+    in_flight: InFlight,
+    next_id: AtomicUsize,
+    reports: broadcast::Sender<ExitReport>,
+}
+
+type InFlight = Mutex<HashMap<usize, (usize, RequestContent)>>;
+
+/// Removes the entry of a request from [`InFlight`] when the caller stops waiting,
+/// including when the future of the request is dropped
+struct InFlightGuard<'a> {
+    in_flight: &'a InFlight,
+    id: usize,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl SupervisedParser {
+    /// Start a supervised NodeJS process
+    ///
+    /// Must be called from within a Tokio runtime.
+    /// Unlike restarts, the initial start is not retried.
+    pub fn new(config: SupervisorConfig) -> Result<SupervisedParser> {
+        SupervisedParser::with_builder(ParserBuilder::new(), config)
+    }
+
+    /// Like [`new`](SupervisedParser::new), with the processes started by `builder`
+    pub fn with_builder(
+        builder: ParserBuilder,
+        config: SupervisorConfig,
+    ) -> Result<SupervisedParser> {
+        let parser = builder.build()?;
+        let (current, _) = watch::channel((parser, 0));
+        let (reported, _) = watch::channel(None);
+        let (gave_up, _) = watch::channel(None);
+        let (reports, _) = broadcast::channel(16);
+        let inner = Arc::new(Inner {
+            builder,
+            config,
+            current,
+            reported,
+            gave_up,
+            in_flight: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+            reports,
+        });
+        tokio::spawn(supervise(Arc::downgrade(&inner)));
+        Ok(SupervisedParser { inner })
+    }
+
+    /// Subscribe to the reports of process exits
+    pub fn exits(&self) -> broadcast::Receiver<ExitReport> {
+        self.inner.reports.subscribe()
+    }
+
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let content = RequestContent::Encode { data, schema };
+        self.request(content).await?.encoded()
+    }
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        let content = RequestContent::Decode { michelson, schema };
+        self.request(content).await?.decoded()
+    }
+
+    /// Encode several values with the same schema, in a single request,
+    /// see [`Parser::encode_batch`]
+    pub async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        let content = RequestContent::EncodeBatch { data, schema };
+        self.request(content).await?.encoded_batch()
+    }
+
+    /// Decode several values with the same schema, in a single request,
+    /// see [`Parser::decode_batch`]
+    pub async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        let content = RequestContent::DecodeBatch { michelson, schema };
+        self.request(content).await?.decoded_batch()
+    }
+
+    async fn request(&self, content: RequestContent) -> Result<ResponseContent> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut attempt = 0;
+        let _guard = InFlightGuard {
+            in_flight: &self.inner.in_flight,
+            id,
+        };
+        loop {
+            let (parser, generation) = self.inner.current.borrow().clone();
+            self.inner
+                .in_flight
+                .lock()
+                .unwrap()
+                .insert(id, (generation, content.clone()));
+            let result = parser.request(content.clone(), parser.timeout()).await;
+            drop(parser);
+            match result {
+                Err(Error::ReadNone) | Err(Error::IoError(_)) => {
+                    // The process exited: the supervisor removes the request when reporting the exit
+                    self.wait_reported(generation).await;
+                    if attempt == self.inner.config.max_retries {
+                        return result;
+                    }
+                    attempt += 1;
+                    self.wait_restarted(generation).await?;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    async fn wait_reported(&self, generation: usize) {
+        let mut reported = self.inner.reported.subscribe();
+        while !matches!(*reported.borrow(), Some(g) if g >= generation) {
+            if reported.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Wait for the process following `generation`,
+    /// fails if the supervisor gave up starting it
+    async fn wait_restarted(&self, generation: usize) -> Result<()> {
+        let mut current = self.inner.current.subscribe();
+        let mut gave_up = self.inner.gave_up.subscribe();
+        loop {
+            if current.borrow().1 > generation {
+                return Ok(());
+            }
+            if let Some((kind, message)) = &*gave_up.borrow() {
+                return Err(Error::IoError(io::Error::new(*kind, message.clone())));
+            }
+            // The senders live as long as `self`
+            tokio::select! {
+                _ = current.changed() => {}
+                _ = gave_up.changed() => {}
+            }
+        }
+    }
+}
+
+/// Wait for the exits of the process, report them and start a new process
+///
+/// Stops when the last [`SupervisedParser`] handle is dropped:
+/// the process exits as its `stdin` is closed.
+/// Also stops after [`SupervisorConfig::max_respawns`] failed attempts to start a new process.
+async fn supervise(inner: Weak<Inner>) {
+    let mut exits = 0;
+    let mut backoff = None;
+    loop {
+        let (exit, generation) = match inner.upgrade() {
+            Some(inner) => {
+                let current = inner.current.borrow();
+                (current.0.exit_receiver(), current.1)
+            }
+            None => return,
+        };
+        let started = Instant::now();
+        let status = wait_exit(exit).await;
+        let supervised = match inner.upgrade() {
+            Some(supervised) => supervised,
+            None => return,
+        };
+        exits += 1;
+        let failed = {
+            let mut in_flight = supervised.in_flight.lock().unwrap();
+            let ids: Vec<usize> = in_flight
+                .iter()
+                .filter(|(_, (g, _))| *g == generation)
+                .map(|(id, _)| *id)
+                .collect();
+            ids.iter()
+                .filter_map(|id| in_flight.remove(id))
+                .map(|(_, content)| content)
+                .collect()
+        };
+        // No subscribers is fine
+        let _ = supervised.reports.send(ExitReport {
+            status,
+            failed,
+            exits,
+        });
+        supervised.reported.send_replace(Some(generation));
+
+        let builder = supervised.builder.clone();
+        let config = supervised.config.clone();
+        drop(supervised);
+        // Back off only if the process keeps exiting shortly after starting
+        backoff = match backoff {
+            Some(delay) if started.elapsed() < config.max_backoff => {
+                Some(config.max_backoff.min(delay * 2))
+            }
+            _ => Some(config.min_backoff),
+        };
+        let mut attempts = 0;
+        let parser = loop {
+            tokio::time::sleep(backoff.unwrap()).await;
+            if inner.upgrade().is_none() {
+                return;
+            }
+            match builder.build() {
+                Ok(parser) => break parser,
+                Err(err) => {
+                    attempts += 1;
+                    if attempts >= config.max_respawns {
+                        let error = match err {
+                            Error::IoError(err) => (err.kind(), err.to_string()),
+                            err => (io::ErrorKind::Other, err.to_string()),
+                        };
+                        if let Some(supervised) = inner.upgrade() {
+                            supervised.gave_up.send_replace(Some(error));
+                        }
+                        return;
+                    }
+                }
+            }
+            backoff = backoff.map(|delay| config.max_backoff.min(delay * 2));
+        };
+        match inner.upgrade() {
+            Some(supervised) => supervised.current.send_replace((parser, generation + 1)),
+            None => return,
+        };
+    }
+}
+
+#[async_trait]
+impl Codec for SupervisedParser {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        SupervisedParser::encode(self, data, schema).await
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        SupervisedParser::decode(self, michelson, schema).await
+    }
+
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        SupervisedParser::encode_batch(self, data, schema).await
+    }
+
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        SupervisedParser::decode_batch(self, michelson, schema).await
+    }
+}
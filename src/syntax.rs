@@ -0,0 +1,601 @@
+//! Parser and printer for the Michelson concrete syntax (the notation of `.tz` files)
+//!
+//! ```
+//! # use zfx_michelson::micheline::Micheline;
+//! let value = Micheline::parse_michelson(r#"Pair 1 (Left "foo") { Elt "a" 2 }"#).unwrap();
+//! assert_eq!(value.to_michelson(), r#"Pair 1 (Left "foo") { Elt "a" 2 }"#);
+//!
+//! let schema = Micheline::parse_michelson("pair (int %x) (nat %y)").unwrap();
+//! assert_eq!(
+//!     schema.to_string().unwrap(),
+//!     r#"{"prim":"pair","args":[{"prim":"int","annots":["%x"]},{"prim":"nat","annots":["%y"]}]}"#
+//! );
+//! ```
+//!
+//! Several top-level expressions separated by `;` (like the `parameter`, `storage` and `code`
+//! sections of a script) are parsed as a sequence. Scripts usually end with a `;`, so a trailing
+//! `;` is accepted and also makes a sequence: `Unit ;` is parsed as `{ Unit }`.
+//!
+//! Parentheses and sequences can be nested at most [`MAX_DEPTH`] levels deep.
+use crate::micheline::Micheline;
+use crate::pack::MAX_DEPTH;
+
+use std::fmt;
+
+/// Error while parsing the Michelson notation, with the (1-based) position of the problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Micheline {
+    /// Parse a value or a type written in the Michelson notation
+    pub fn parse_michelson(s: &str) -> Result<Micheline, ParseError> {
+        let tokens = Lexer::new(s).tokenize()?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        parser.parse_toplevel()
+    }
+
+    /// Print in the Michelson notation, on a single line
+    pub fn to_michelson(&self) -> String {
+        let mut out = String::new();
+        print(self, false, &mut out);
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(String),
+    Str(String),
+    Bytes(String),
+    Ident(String),
+    Annot(String),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Semi,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Int(i) => write!(f, "number {}", i),
+            Token::Str(s) => write!(f, "string {:?}", s),
+            Token::Bytes(b) => write!(f, "bytes 0x{}", b),
+            Token::Ident(i) => write!(f, "`{}`", i),
+            Token::Annot(a) => write!(f, "annotation `{}`", a),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LBrace => write!(f, "`{{`"),
+            Token::RBrace => write!(f, "`}}`"),
+            Token::Semi => write!(f, "`;`"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(s: &'a str) -> Self {
+        Lexer {
+            chars: s.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line,
+            column: self.column,
+            message,
+        })
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn take_while(&mut self, mut pred: impl FnMut(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !pred(c) {
+                break;
+            }
+            s.push(c);
+            self.next_char();
+        }
+        s
+    }
+
+    fn skip_comment(&mut self) -> Result<(), ParseError> {
+        // `/*` was already consumed
+        loop {
+            match self.next_char() {
+                Some('*') if self.chars.peek() == Some(&'/') => {
+                    self.next_char();
+                    return Ok(());
+                }
+                Some(_) => (),
+                None => return self.error("unterminated comment".to_owned()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        // the opening `"` was already consumed
+        let mut s = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next_char() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some(c) => return self.error(format!("invalid escape sequence `\\{}`", c)),
+                    None => return self.error("unterminated string".to_owned()),
+                },
+                Some('\n') | None => return self.error("unterminated string".to_owned()),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize, usize)>, ParseError> {
+        let mut tokens = vec![];
+        loop {
+            let (line, column) = (self.line, self.column);
+            let c = match self.chars.peek() {
+                None => {
+                    tokens.push((Token::Eof, line, column));
+                    return Ok(tokens);
+                }
+                Some(&c) => c,
+            };
+            let token = match c {
+                c if c.is_whitespace() => {
+                    self.next_char();
+                    continue;
+                }
+                '#' => {
+                    self.take_while(|c| c != '\n');
+                    continue;
+                }
+                '/' => {
+                    self.next_char();
+                    if self.next_char() != Some('*') {
+                        return self.error("expected `/*`".to_owned());
+                    }
+                    self.skip_comment()?;
+                    continue;
+                }
+                '(' | ')' | '{' | '}' | ';' => {
+                    self.next_char();
+                    match c {
+                        '(' => Token::LParen,
+                        ')' => Token::RParen,
+                        '{' => Token::LBrace,
+                        '}' => Token::RBrace,
+                        _ => Token::Semi,
+                    }
+                }
+                '"' => {
+                    self.next_char();
+                    Token::Str(self.string()?)
+                }
+                '@' | ':' | '%' | '&' | '$' => Token::Annot(
+                    self.take_while(|c| c.is_ascii_alphanumeric() || "@:%&$_.".contains(c)),
+                ),
+                '-' | '0'..='9' => {
+                    let number = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+                    if let Some(hex) = number.strip_prefix("0x") {
+                        if !hex.chars().all(|c| c.is_ascii_hexdigit()) || hex.len() % 2 == 1 {
+                            return self.error(format!("invalid bytes `{}`", number));
+                        }
+                        Token::Bytes(hex.to_owned())
+                    } else {
+                        let digits = number.strip_prefix('-').unwrap_or(&number);
+                        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                            return self.error(format!("invalid number `{}`", number));
+                        }
+                        Token::Int(number)
+                    }
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    Token::Ident(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_'))
+                }
+                c => return self.error(format!("unexpected character `{}`", c)),
+            };
+            tokens.push((token, line, column));
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+    /// Number of enclosing parentheses and sequences
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        let (_, line, column) = self.tokens[self.pos];
+        Err(ParseError {
+            line,
+            column,
+            message,
+        })
+    }
+
+    fn unexpected<T>(&self) -> Result<T, ParseError> {
+        self.error(format!("unexpected {}", self.peek()))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            self.error(format!("expected {}, found {}", token, self.peek()))
+        }
+    }
+
+    /// A single expression, or a sequence of expressions separated or terminated by `;`
+    fn parse_toplevel(&mut self) -> Result<Micheline, ParseError> {
+        let first = self.parse_expr()?;
+        if *self.peek() == Token::Eof {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while *self.peek() == Token::Semi {
+            self.next();
+            if *self.peek() == Token::Eof {
+                break;
+            }
+            items.push(self.parse_expr()?);
+        }
+        if *self.peek() != Token::Eof {
+            return self.unexpected();
+        }
+        Ok(Micheline::Seq(items))
+    }
+
+    /// A primitive application or a single argument
+    fn parse_expr(&mut self) -> Result<Micheline, ParseError> {
+        if let Token::Ident(_) = self.peek() {
+            self.parse_application()
+        } else {
+            self.parse_arg()
+        }
+    }
+
+    fn parse_application(&mut self) -> Result<Micheline, ParseError> {
+        let prim = match self.next() {
+            Token::Ident(prim) => prim,
+            _ => unreachable!("parse_application is called on identifiers"),
+        };
+        let mut annots = vec![];
+        while let Token::Annot(annot) = self.peek() {
+            annots.push(annot.clone());
+            self.next();
+        }
+        let mut args = vec![];
+        loop {
+            match self.peek() {
+                Token::RParen | Token::RBrace | Token::Semi | Token::Eof => break,
+                Token::Annot(_) => {
+                    return self.error("annotations must follow the primitive".to_owned())
+                }
+                _ => args.push(self.parse_arg()?),
+            }
+        }
+        Ok(Micheline::Prim { prim, args, annots })
+    }
+
+    fn parse_arg(&mut self) -> Result<Micheline, ParseError> {
+        match self.peek().clone() {
+            Token::Int(int) => {
+                self.next();
+                Ok(Micheline::Number { int })
+            }
+            Token::Str(string) => {
+                self.next();
+                Ok(Micheline::String { string })
+            }
+            Token::Bytes(bytes) => {
+                self.next();
+                Ok(Micheline::Bytes { bytes })
+            }
+            Token::Ident(prim) => {
+                self.next();
+                Ok(Micheline::Prim {
+                    prim,
+                    args: vec![],
+                    annots: vec![],
+                })
+            }
+            Token::LParen => self.nested(|parser| {
+                parser.next();
+                let expr = parser.parse_expr()?;
+                parser.expect(Token::RParen)?;
+                Ok(expr)
+            }),
+            Token::LBrace => self.nested(Parser::parse_seq),
+            _ => self.unexpected(),
+        }
+    }
+
+    /// Parse a parenthesised expression or a sequence, at most [`MAX_DEPTH`] levels deep
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Parser) -> Result<Micheline, ParseError>,
+    ) -> Result<Micheline, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return self.error(format!("nested deeper than {} levels", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_seq(&mut self) -> Result<Micheline, ParseError> {
+        self.expect(Token::LBrace)?;
+        let mut items = vec![];
+        loop {
+            if *self.peek() == Token::RBrace {
+                break;
+            }
+            items.push(self.parse_expr()?);
+            match self.peek() {
+                Token::Semi => {
+                    self.next();
+                }
+                Token::RBrace => break,
+                _ => return self.error(format!("expected `;` or `}}`, found {}", self.peek())),
+            }
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Micheline::Seq(items))
+    }
+}
+
+/// Print `m`, with parentheses if it's the argument of a primitive
+///
+/// The recursion is bounded by the depth of the value, which is limited when it's read
+/// from the Michelson notation, from JSON or from its binary form.
+fn print(m: &Micheline, as_arg: bool, out: &mut String) {
+    match m {
+        Micheline::Number { int } => out.push_str(int),
+        Micheline::String { string } => {
+            out.push('"');
+            for c in string.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    '\r' => out.push_str("\\r"),
+                    '\u{8}' => out.push_str("\\b"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        Micheline::Bytes { bytes } => {
+            out.push_str("0x");
+            out.push_str(bytes.strip_prefix("0x").unwrap_or(bytes));
+        }
+        Micheline::Seq(items) if items.is_empty() => out.push_str("{}"),
+        Micheline::Seq(items) => {
+            out.push_str("{ ");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" ; ");
+                }
+                print(item, false, out);
+            }
+            out.push_str(" }");
+        }
+        Micheline::Prim { prim, args, annots } => {
+            let parens = as_arg && !(args.is_empty() && annots.is_empty());
+            if parens {
+                out.push('(');
+            }
+            out.push_str(prim);
+            for annot in annots {
+                out.push(' ');
+                out.push_str(annot);
+            }
+            for arg in args {
+                out.push(' ');
+                print(arg, true, out);
+            }
+            if parens {
+                out.push(')');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn prim(prim: &str, args: Vec<Micheline>, annots: &[&str]) -> Micheline {
+        Micheline::Prim {
+            prim: prim.to_owned(),
+            args,
+            annots: annots.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn int(i: &str) -> Micheline {
+        Micheline::Number { int: i.to_owned() }
+    }
+
+    fn string(s: &str) -> Micheline {
+        Micheline::String {
+            string: s.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let m = Micheline::parse_michelson(r#"Pair 1 (Left "foo") { Elt "a" 2 }"#).unwrap();
+        let expected = prim(
+            "Pair",
+            vec![
+                int("1"),
+                prim("Left", vec![string("foo")], &[]),
+                Micheline::Seq(vec![prim("Elt", vec![string("a"), int("2")], &[])]),
+            ],
+            &[],
+        );
+        assert_eq!(m, expected);
+        assert_eq!(m.to_michelson(), r#"Pair 1 (Left "foo") { Elt "a" 2 }"#);
+    }
+
+    #[test]
+    fn test_parse_type() {
+        let m = Micheline::parse_michelson("pair %p (int %x) (nat %y)").unwrap();
+        let expected = prim(
+            "pair",
+            vec![prim("int", vec![], &["%x"]), prim("nat", vec![], &["%y"])],
+            &["%p"],
+        );
+        assert_eq!(m, expected);
+        assert_eq!(m.to_michelson(), "pair %p (int %x) (nat %y)");
+    }
+
+    #[test]
+    fn test_parse_same_as_json() {
+        let text = "or (or (int %decrement) (int %increment)) (unit %reset)";
+        let json = r#"
+        { "prim": "or",
+          "args":
+            [ { "prim": "or",
+                "args":
+                  [ { "prim": "int", "annots": [ "%decrement" ] },
+                    { "prim": "int", "annots": [ "%increment" ] } ] },
+              { "prim": "unit", "annots": [ "%reset" ] } ] } "#;
+        assert_eq!(
+            Micheline::parse_michelson(text).unwrap(),
+            Micheline::from_str(json).unwrap()
+        );
+        assert_eq!(Micheline::from_str(json).unwrap().to_michelson(), text);
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        assert_eq!(Micheline::parse_michelson("-42").unwrap(), int("-42"));
+        assert_eq!(
+            Micheline::parse_michelson("0xDEADbeef").unwrap(),
+            Micheline::Bytes {
+                bytes: "DEADbeef".to_owned()
+            }
+        );
+        let s = Micheline::parse_michelson(r#""a\"b\\c\nd""#).unwrap();
+        assert_eq!(s, string("a\"b\\c\nd"));
+        assert_eq!(s.to_michelson(), r#""a\"b\\c\nd""#);
+        assert_eq!(
+            Micheline::parse_michelson("{}").unwrap(),
+            Micheline::Seq(vec![])
+        );
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = "
+            parameter int ; # the parameter
+            storage /* a comment */ int ;
+            code { CAR ; NIL operation ; PAIR } ;";
+        let m = Micheline::parse_michelson(script).unwrap();
+        assert_eq!(
+            m.to_michelson(),
+            "{ parameter int ; storage int ; code { CAR ; NIL operation ; PAIR } }"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Micheline::parse_michelson("Pair 1\n  (Left 2").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 10));
+        let err = Micheline::parse_michelson("Pair 1 \"foo").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 12));
+        let err = Micheline::parse_michelson("{ Elt 1 2 ; ) }").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 13));
+        let err = Micheline::parse_michelson("Pair 12abc").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(Micheline::parse_michelson("").is_err());
+        assert!(Micheline::parse_michelson("pair int %x").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let nested = |depth| format!("{}Unit{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(Micheline::parse_michelson(&nested(MAX_DEPTH)).is_ok());
+        let err = Micheline::parse_michelson(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert_eq!((err.line, err.column), (1, MAX_DEPTH + 1));
+        let deep = format!("{}{}", "(".repeat(200_000), "{".repeat(100_000));
+        assert!(Micheline::parse_michelson(&deep).is_err());
+        let seqs = format!("{}{}", "{".repeat(MAX_DEPTH + 1), "}".repeat(MAX_DEPTH + 1));
+        assert!(Micheline::parse_michelson(&seqs).is_err());
+    }
+
+    #[test]
+    fn test_parse_trailing_semicolon() {
+        let unit = prim("Unit", vec![], &[]);
+        assert_eq!(
+            Micheline::parse_michelson("Unit ;").unwrap(),
+            Micheline::Seq(vec![unit.clone()])
+        );
+        assert_eq!(Micheline::parse_michelson("Unit").unwrap(), unit);
+        assert!(Micheline::parse_michelson("Unit ; ;").is_err());
+    }
+}
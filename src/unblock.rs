@@ -0,0 +1,199 @@
+//! Async decoder/encoder for any runtime ([`Parser`]), e.g. async-std or smol
+//!
+//! Requires the `unblock` feature. The requests are handled by a [`blocking::Parser`]
+//! on the thread pool of the [`blocking`](unblocking) crate, so no particular runtime is needed.
+//!
+//! ```no_run
+//! # async fn run() -> zfx_michelson::Result<()> {
+//! use serde_json::json;
+//! use zfx_michelson::unblock::{install_parser, Parser};
+//!
+//! install_parser().await?;
+//! let parser = Parser::new()?;
+//! let decoded = parser.decode(json!({ "int": "-26" }), json!({ "prim": "int" })).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::blocking;
+use crate::codec::Codec;
+use crate::michelson::{MichelsonV1Expression, SchemaHandle};
+use crate::{ParserBuilder, Result};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Install the necessary Javascript code in the current directory,
+/// see [`blocking::install_parser`]
+pub async fn install_parser() -> Result<PathBuf> {
+    unblocking::unblock(blocking::install_parser).await
+}
+
+/// Install the necessary Javascript code in `dir`, or in a private temporary directory,
+/// see [`blocking::install_parser_in`]
+pub async fn install_parser_in(dir: Option<&Path>) -> Result<PathBuf> {
+    let dir = dir.map(Path::to_owned);
+    unblocking::unblock(move || blocking::install_parser_in(dir.as_deref())).await
+}
+
+/// A [`blocking::Parser`] with an async interface
+///
+/// This is a handle that can be cloned and shared between tasks,
+/// requests are handled one at a time.
+/// The NodeJS process is killed when the last handle is dropped.
+#[derive(Clone)]
+pub struct Parser {
+    inner: Arc<blocking::Parser>,
+}
+
+impl Parser {
+    /// Create a new `Parser` instance, **this starts a new NodeJS process**
+    ///
+    /// The default settings of [`ParserBuilder`] are used.
+    pub fn new() -> Result<Parser> {
+        Parser::with_builder(&ParserBuilder::new())
+    }
+
+    /// Start a NodeJS process with the settings of `builder`
+    pub fn with_builder(builder: &ParserBuilder) -> Result<Parser> {
+        Ok(Parser {
+            inner: Arc::new(blocking::Parser::with_builder(builder)?),
+        })
+    }
+
+    /// Encode a [value][`serde_json::Value] to the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.encode(data, schema)).await
+    }
+
+    /// Decode a [value][`serde_json::Value] from the JSON-based Michelson format
+    /// according to the data schema provided
+    pub async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.decode(michelson, schema)).await
+    }
+
+    /// Encode several values with the same schema, in a single request,
+    /// see [`blocking::Parser::encode_batch`]
+    pub async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.encode_batch(data, schema)).await
+    }
+
+    /// Decode several values with the same schema, in a single request,
+    /// see [`blocking::Parser::decode_batch`]
+    pub async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.decode_batch(michelson, schema)).await
+    }
+
+    /// Register `schema` in the parser process, see [`blocking::Parser::register_schema`]
+    pub async fn register_schema(&self, schema: MichelsonV1Expression) -> Result<SchemaHandle> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.register_schema(schema)).await
+    }
+
+    /// Forget a registered schema, returns `false` if it wasn't registered
+    pub async fn unregister_schema(&self, handle: SchemaHandle) -> Result<bool> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.unregister_schema(handle)).await
+    }
+
+    /// Like [`encode`](Parser::encode), with a [registered](Parser::register_schema) schema
+    pub async fn encode_registered(
+        &self,
+        data: Value,
+        handle: SchemaHandle,
+    ) -> Result<MichelsonV1Expression> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.encode_registered(data, handle)).await
+    }
+
+    /// Like [`decode`](Parser::decode), with a [registered](Parser::register_schema) schema
+    pub async fn decode_registered(
+        &self,
+        michelson: MichelsonV1Expression,
+        handle: SchemaHandle,
+    ) -> Result<Value> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.decode_registered(michelson, handle)).await
+    }
+
+    /// Serialise `data`, a Michelson value of type `schema`, like the `PACK` instruction,
+    /// see [`blocking::Parser::pack`]
+    pub async fn pack(
+        &self,
+        data: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<u8>> {
+        let inner = self.inner.clone();
+        unblocking::unblock(move || inner.pack(data, schema)).await
+    }
+
+    /// Deserialise packed `bytes` to a Michelson value of type `schema`,
+    /// see [`blocking::Parser::unpack`]
+    pub async fn unpack(
+        &self,
+        bytes: &[u8],
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        let inner = self.inner.clone();
+        let bytes = bytes.to_vec();
+        unblocking::unblock(move || inner.unpack(&bytes, schema)).await
+    }
+}
+
+#[async_trait]
+impl Codec for Parser {
+    async fn encode(
+        &self,
+        data: Value,
+        schema: MichelsonV1Expression,
+    ) -> Result<MichelsonV1Expression> {
+        Parser::encode(self, data, schema).await
+    }
+
+    async fn decode(
+        &self,
+        michelson: MichelsonV1Expression,
+        schema: MichelsonV1Expression,
+    ) -> Result<Value> {
+        Parser::decode(self, michelson, schema).await
+    }
+
+    async fn encode_batch(
+        &self,
+        data: Vec<Value>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<MichelsonV1Expression>>> {
+        Parser::encode_batch(self, data, schema).await
+    }
+
+    async fn decode_batch(
+        &self,
+        michelson: Vec<MichelsonV1Expression>,
+        schema: MichelsonV1Expression,
+    ) -> Result<Vec<Result<Value>>> {
+        Parser::decode_batch(self, michelson, schema).await
+    }
+}
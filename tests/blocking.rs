@@ -0,0 +1,61 @@
+//! The `blocking` and `unblock` parsers, tested with a stand-in for the parser bundle
+#![cfg(feature = "blocking")]
+use serde_json::json;
+use zfx_michelson::{Error, ParserBuilder};
+
+mod common;
+use common::echo_dir;
+
+#[test]
+fn blocking_parser() {
+    use zfx_michelson::blocking::Parser;
+
+    let dir = echo_dir("blocking");
+    let builder = ParserBuilder::new().bundle(dir.join("echo.js"));
+    let parser = Parser::with_builder(&builder).unwrap();
+    for i in 0..4 {
+        let value = parser.decode(json!(i), json!(null)).unwrap();
+        assert_eq!(value, json!(i));
+    }
+    let results = parser
+        .decode_batch(vec![json!(1), json!({ "error": "oops" })], json!(null))
+        .unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), &json!(1));
+    assert!(matches!(results[1], Err(Error::DecodeError { .. })));
+    let packed = parser.pack(json!({ "int": "1" }), json!(null)).unwrap();
+    let unpacked = parser.unpack(&packed, json!(null)).unwrap();
+    assert_eq!(unpacked, json!({ "int": "1" }));
+    // Lines that aren't the response are skipped
+    let value = parser.decode(json!("stray"), json!(null)).unwrap();
+    assert_eq!(value, json!("stray"));
+    let value = parser.decode(json!("next"), json!(null)).unwrap();
+    assert_eq!(value, json!("next"));
+    let response = parser.decode(json!("exit"), json!(null));
+    assert!(matches!(response, Err(Error::ReadNone)));
+}
+
+#[cfg(feature = "unblock")]
+#[test]
+fn unblock_parser() {
+    use zfx_michelson::codec::Codec;
+    use zfx_michelson::unblock::Parser;
+
+    let dir = echo_dir("unblock");
+    let builder = ParserBuilder::new().bundle(dir.join("echo.js"));
+    let parser = Parser::with_builder(&builder).unwrap();
+    // No runtime needed, a simple executor is enough
+    futures_lite::future::block_on(async {
+        let requests = (0..4).map(|i| {
+            let parser = parser.clone();
+            async move { parser.decode(json!(i), json!(null)).await }
+        });
+        for (i, request) in requests.enumerate() {
+            assert_eq!(request.await.unwrap(), json!(i));
+        }
+        let codec: &dyn Codec = &parser;
+        let value = codec.decode(json!("codec"), json!(null)).await.unwrap();
+        assert_eq!(value, json!("codec"));
+        let response = parser.decode(json!("exit"), json!(null)).await;
+        assert!(matches!(response, Err(Error::ReadNone)));
+    });
+}
@@ -0,0 +1,238 @@
+//! Process management, tested with a stand-in for the parser bundle
+#![cfg(feature = "async-tokio")]
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use zfx_michelson::{Error, ParserBuilder, SupervisedParser, SupervisorConfig};
+
+mod common;
+use common::echo_dir;
+
+#[tokio::test]
+async fn builder_settings() {
+    let dir = echo_dir("settings");
+    let parser = ParserBuilder::new()
+        .current_dir(&dir)
+        .bundle("echo.js")
+        .env("ZFX_TEST", "yes")
+        .arg("--no-warnings")
+        .build()
+        .unwrap();
+    let value = parser.encode(json!(null), json!(null)).await.unwrap();
+    assert_eq!(value["env"], json!("yes"));
+    assert_eq!(value["args"], json!(["--no-warnings"]));
+    let cwd = PathBuf::from(value["cwd"].as_str().unwrap());
+    assert_eq!(cwd.canonicalize().unwrap(), dir.canonicalize().unwrap());
+}
+
+#[tokio::test]
+async fn builder_errors() {
+    let dir = echo_dir("errors");
+    let missing_bundle = ParserBuilder::new().current_dir(&dir).bundle("missing.js");
+    assert!(matches!(missing_bundle.build(), Err(Error::IoError(_))));
+    let missing_node = ParserBuilder::new()
+        .node(dir.join("no-such-node"))
+        .bundle(dir.join("echo.js"));
+    assert!(matches!(missing_node.build(), Err(Error::IoError(_))));
+}
+
+#[tokio::test]
+async fn parser_exit() {
+    let dir = echo_dir("exit");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .build()
+        .unwrap();
+    let response = parser.decode(json!("exit"), json!(null)).await;
+    assert!(matches!(response, Err(Error::ReadNone)));
+    assert_eq!(parser.exited().await.and_then(|s| s.code()), Some(3));
+}
+
+#[tokio::test]
+async fn supervised_restart() {
+    let dir = echo_dir("restart");
+    let builder = ParserBuilder::new().bundle(dir.join("echo.js"));
+    let config = SupervisorConfig {
+        max_retries: 1,
+        min_backoff: std::time::Duration::from_millis(10),
+        ..Default::default()
+    };
+    let parser = SupervisedParser::with_builder(builder, config).unwrap();
+    let mut exits = parser.exits();
+
+    // Retried once, and fails again
+    let response = parser.decode(json!("exit"), json!(null)).await;
+    assert!(matches!(response, Err(Error::ReadNone)));
+    for n in 1..=2 {
+        let report = exits.recv().await.unwrap();
+        assert_eq!(report.exits, n);
+        assert_eq!(report.status.and_then(|s| s.code()), Some(3));
+        assert_eq!(report.failed.len(), 1);
+    }
+
+    // The process is restarted
+    let value: Value = parser.decode(json!("ok"), json!(null)).await.unwrap();
+    assert_eq!(value, json!("ok"));
+}
+
+#[tokio::test]
+async fn supervised_gives_up() {
+    let dir = echo_dir("gives-up");
+    let builder = ParserBuilder::new().bundle(dir.join("echo.js"));
+    let config = SupervisorConfig {
+        max_retries: 1,
+        min_backoff: std::time::Duration::from_millis(10),
+        max_backoff: std::time::Duration::from_millis(20),
+        max_respawns: 3,
+    };
+    let parser = SupervisedParser::with_builder(builder, config).unwrap();
+    let value: Value = parser.decode(json!("ok"), json!(null)).await.unwrap();
+    assert_eq!(value, json!("ok"));
+
+    // The process cannot be restarted without its bundle
+    std::fs::remove_file(dir.join("echo.js")).unwrap();
+    let response = parser.decode(json!("exit"), json!(null)).await;
+    assert!(matches!(response, Err(Error::IoError(_))));
+    let response = parser.decode(json!("ok"), json!(null)).await;
+    assert!(matches!(response, Err(Error::IoError(_))));
+}
+
+#[tokio::test]
+async fn parser_shutdown() {
+    let dir = echo_dir("shutdown");
+    let builder = ParserBuilder::new().bundle(dir.join("echo.js"));
+    let timeout = std::time::Duration::from_secs(5);
+
+    let parser = builder.build().unwrap();
+    let status = parser.shutdown(timeout).await.unwrap();
+    assert!(status.success());
+    let response = parser.decode(json!("ok"), json!(null)).await;
+    assert!(matches!(response, Err(Error::IoError(_))));
+
+    // Killed after the timeout
+    let parser = builder.build().unwrap();
+    parser.decode(json!("hang"), json!(null)).await.unwrap();
+    let status = parser
+        .shutdown(std::time::Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[tokio::test]
+async fn parser_timeouts() {
+    use std::time::Duration;
+
+    let dir = echo_dir("timeouts");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let slow = json!({ "delay": 300 });
+    let response = parser.decode(slow.clone(), json!(null)).await;
+    assert!(matches!(response, Err(Error::Timeout)));
+    let response = parser
+        .decode_with_timeout(slow.clone(), json!(null), Some(Duration::from_secs(5)))
+        .await;
+    assert_eq!(response.unwrap(), slow);
+
+    // An abandoned request doesn't affect the following ones, its response is discarded
+    tokio::select! {
+        _ = parser.decode(json!({ "delay": 50 }), json!(null)) => panic!("not abandoned"),
+        _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = parser.decode(json!("next"), json!(null)).await;
+    assert_eq!(response.unwrap(), json!("next"));
+}
+
+#[tokio::test]
+async fn parser_errors() {
+    let dir = echo_dir("errors");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .build()
+        .unwrap();
+    let error = json!({
+        "class": "AddressValidationError",
+        "message": "[owner] Address is not valid: tz1",
+        "path": ["owner"],
+        "field": "owner",
+        "value": "tz1",
+    });
+    let response = parser.decode(json!({ "error": error }), json!(null)).await;
+    match response {
+        Err(Error::InvalidAddress(error)) => {
+            assert_eq!(error.path, Some(vec!["owner".to_string()]));
+            assert_eq!(error.value, Some(json!("tz1")));
+        }
+        _ => panic!("unexpected response: {:?}", response),
+    }
+    // Unstructured errors
+    let response = parser.decode(json!({ "error": "oops" }), json!(null)).await;
+    assert!(matches!(response, Err(Error::DecodeError { error }) if error == json!("oops")));
+}
+
+#[tokio::test]
+async fn parser_batch() {
+    let dir = echo_dir("batch");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .build()
+        .unwrap();
+    let batch = vec![json!(1), json!({ "error": "oops" }), json!(3)];
+    let results = parser.decode_batch(batch, json!(null)).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap(), &json!(1));
+    assert!(matches!(&results[1], Err(Error::DecodeError { error }) if error == &json!("oops")));
+    assert_eq!(results[2].as_ref().unwrap(), &json!(3));
+
+    let results = parser.decode_batch(vec![], json!(null)).await.unwrap();
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn parser_registered_schemas() {
+    let dir = echo_dir("registered");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .build()
+        .unwrap();
+    let first = parser
+        .register_schema(json!({ "prim": "int" }))
+        .await
+        .unwrap();
+    let second = parser
+        .register_schema(json!({ "prim": "nat" }))
+        .await
+        .unwrap();
+    assert_ne!(first, second);
+    let value = parser.decode_registered(json!(1), first).await.unwrap();
+    assert_eq!(value, json!(1));
+
+    assert!(parser.unregister_schema(first).await.unwrap());
+    assert!(!parser.unregister_schema(first).await.unwrap());
+    let response = parser.decode_registered(json!(1), first).await;
+    assert!(matches!(response, Err(Error::UnknownSchema)));
+    let value = parser.decode_registered(json!(2), second).await.unwrap();
+    assert_eq!(value, json!(2));
+}
+
+#[tokio::test]
+async fn parser_pack() {
+    let dir = echo_dir("pack");
+    let parser = ParserBuilder::new()
+        .bundle(dir.join("echo.js"))
+        .build()
+        .unwrap();
+    let schema = json!({ "prim": "int" });
+    let packed = parser
+        .pack(json!({ "int": "1" }), schema.clone())
+        .await
+        .unwrap();
+    assert_eq!(packed[0], 0x05);
+    let unpacked = parser.unpack(&packed, schema.clone()).await.unwrap();
+    assert_eq!(unpacked, json!({ "int": "1" }));
+    let response = parser.unpack(&[0x00], schema).await;
+    assert!(matches!(response, Err(Error::DecodeError { .. })));
+}
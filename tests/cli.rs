@@ -0,0 +1,129 @@
+//! The `zfx-michelson` command-line tool, with the native backend
+#![cfg(feature = "cli")]
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn zfx_michelson(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zfx-michelson"))
+        .args(["--backend", "native"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn cli_conversions() {
+    let dir = std::env::temp_dir().join(format!("zfx-michelson-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let schema = dir.join("schema.tz");
+    std::fs::write(&schema, "pair (int %a) (string %b)").unwrap();
+    let schema = schema.to_str().unwrap();
+
+    // NDJSON, JSON and text notation, one result per line
+    let input =
+        "Pair 1 \"x\"\n\n{\"prim\":\"Pair\",\"args\":[{\"int\":\"2\"},{\"string\":\"y\"}]}\n";
+    let output = zfx_michelson(&["decode", "--schema", schema], input);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "{\"a\":\"1\",\"b\":\"x\"}\n{\"a\":\"2\",\"b\":\"y\"}\n"
+    );
+
+    // A failing line doesn't stop the conversion
+    let output = zfx_michelson(&["decode", "-s", schema], "Unit\nPair 3 \"z\"\n");
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "{\"a\":\"3\",\"b\":\"z\"}\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("line 1:"));
+
+    let value = dir.join("value.json");
+    std::fs::write(&value, "{ \"a\": \"4\", \"b\": \"w\" }").unwrap();
+    let value = value.to_str().unwrap();
+    let output = zfx_michelson(&["encode", "--text", "-s", schema, value], "");
+    assert_eq!(stdout(&output), "Pair 4 \"w\"\n");
+
+    // Scalar literals are valid JSON, but read in the Michelson notation
+    let int = dir.join("int.tz");
+    std::fs::write(&int, "int").unwrap();
+    let output = zfx_michelson(&["decode", "-s", int.to_str().unwrap()], "42\n-5\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "\"42\"\n\"-5\"\n");
+
+    let output = zfx_michelson(&["convert", "--to", "json"], "Left 5\n");
+    assert_eq!(
+        stdout(&output),
+        "{\"args\":[{\"int\":\"5\"}],\"prim\":\"Left\"}\n"
+    );
+    let output = zfx_michelson(&["convert", "--to", "text"], stdout(&output));
+    assert_eq!(stdout(&output), "Left 5\n");
+}
+
+#[test]
+fn cli_repl() {
+    let input = "\
+:schema pair (int %a) (string %b)
+(Pair 1
+  \"x\")
+{ \"a\": \"2\", \"b\": \"y\" }
+:history
+!2
+";
+    let output = zfx_michelson(&["repl"], input);
+    assert!(output.status.success());
+    let output = stdout(&output);
+    assert!(output.contains("a: int\nb: string\n"));
+    assert!(output.contains("{\n  \"a\": \"1\",\n  \"b\": \"x\"\n}\n"));
+    assert!(output.contains("Pair 2 \"y\"\n"));
+    assert!(output.contains("   3  { \"a\": \"2\", \"b\": \"y\" }\n"));
+    assert!(output.ends_with("(Pair 1\n  \"x\")\n{\n  \"a\": \"1\",\n  \"b\": \"x\"\n}\n> "));
+}
+
+#[test]
+fn cli_repl_scalars() {
+    // Valid JSON that isn't Micheline is encoded, Michelson values are decoded
+    let input = "\
+:schema option int
+null
+\"5\"
+Some 6
+:schema bool
+true
+:schema int
+42
+{ \"int\": \"7\" }
+";
+    let output = zfx_michelson(&["repl"], input);
+    assert!(output.status.success());
+    let lines: Vec<&str> = stdout(&output)
+        .split("> ")
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "(no fields): option int",
+            "None",
+            "Some 5",
+            "\"6\"",
+            "(no fields): bool",
+            "True",
+            "(no fields): int",
+            "42",
+            "\"7\""
+        ]
+    );
+}
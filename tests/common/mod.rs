@@ -0,0 +1,67 @@
+//! A stand-in for the parser bundle, shared by the integration tests
+use std::path::PathBuf;
+
+/// Responds to `Decode` requests with the `michelson` field, exits on `"exit"`,
+/// keeps running after `stdin` is closed on `"hang"`, responds after `delay` ms to `{ "delay": ms }`,
+/// fails with `error` on `{ "error": error }`, responds to `DecodeBatch` requests item by item,
+/// keeps track of registered schemas, packs values as `05` followed by their JSON in hex,
+/// writes a stray line and a response with a stale ID before responding to `"stray"`,
+/// and responds to `Encode` requests with its environment and arguments
+pub const ECHO_BUNDLE: &str = r#"
+const readline = require("readline");
+const lines = readline.createInterface({ input: process.stdin });
+const schemas = new Set();
+let nextHandle = 0;
+const unknown = { class: "UnknownSchemaError", message: "", path: null, field: null };
+lines.on("line", (line) => {
+  const { id, content } = JSON.parse(line);
+  if (content.kind === "RegisterSchema") {
+    schemas.add(nextHandle);
+    content.michelson = nextHandle++;
+  }
+  if (content.kind === "UnregisterSchema") {
+    content.michelson = schemas.delete(content.handle);
+  }
+  if (content.kind === "DecodeRegistered" && !schemas.has(content.handle)) {
+    content.michelson = { error: unknown };
+  }
+  if (content.kind === "Pack") {
+    content.michelson = "05" + Buffer.from(JSON.stringify(content.data)).toString("hex");
+  }
+  if (content.kind === "Unpack") {
+    content.michelson = content.bytes.startsWith("05")
+      ? JSON.parse(Buffer.from(content.bytes.slice(2), "hex").toString())
+      : { error: { class: "Error", message: "missing prefix", path: null, field: null } };
+  }
+  if (content.kind === "Decode" && content.michelson === "exit") {
+    process.exit(3);
+  }
+  if (content.kind === "Decode" && content.michelson === "stray") {
+    process.stdout.write("not a response\n");
+    process.stdout.write(JSON.stringify({ id: id - 1, content: { status: "Success", value: 0 } }) + "\n");
+  }
+  if (content.kind === "Decode" && content.michelson === "hang") {
+    setInterval(() => {}, 1000);
+  }
+  const answer = (value) =>
+    value && value.error ? { status: "Error", error: value.error } : { status: "Success", value };
+  const value =
+    content.michelson !== undefined && content.kind !== "DecodeBatch"
+      ? content.michelson
+      : { env: process.env.ZFX_TEST || null, args: process.execArgv, cwd: process.cwd() };
+  const result =
+    content.kind === "DecodeBatch"
+      ? { status: "Success", value: content.michelson.map(answer) }
+      : answer(value);
+  const respond = () => process.stdout.write(JSON.stringify({ id, content: result }) + "\n");
+  setTimeout(respond, (value && value.delay) || 0);
+});
+"#;
+
+/// A fresh directory containing `echo.js`
+pub fn echo_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zfx-michelson-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("echo.js"), ECHO_BUNDLE).unwrap();
+    dir
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "async-tokio")]
 use serde::{Deserialize, Serialize};
 use serde_json::*;
 use std::collections::HashSet;
@@ -206,8 +207,8 @@ struct Storage {
 
 #[tokio::test]
 async fn complex_storage() {
-    install_parser().await;
-    let mut p = Parser::new();
+    install_parser().await.unwrap();
+    let p = Parser::new();
 
     let schema: Value = serde_json::from_str(SCHEMA).unwrap();
     println!("SCHEMA: {}", schema);
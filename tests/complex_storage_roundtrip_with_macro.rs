@@ -1,3 +1,4 @@
+#![cfg(feature = "async-tokio")]
 use serde::{Deserialize, Serialize};
 use serde_json::*;
 use std::collections::{HashMap, HashSet};
@@ -30,8 +31,8 @@ wrapped_struct! { Storage {
 
 #[tokio::test]
 async fn complex_storage_roundtrip_with_macro() {
-    install_parser().await;
-    let mut p = Parser::new();
+    install_parser().await.unwrap();
+    let p = Parser::new();
 
     let schema: Value = Storage::get_schema().unwrap();
     println!("SCHEMA: {}", schema);
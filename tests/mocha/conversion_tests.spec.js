@@ -1,4 +1,4 @@
-const { jsonEncode, jsonDecode, JsonUnit } = require("../../src/json_converter");
+const { jsonEncode, jsonDecode, jsonDecoder, jsonError, JsonUnit } = require("../../src/json_converter");
 const assert = require("chai").assert;
 const register_schema = require("./test_data").register_schema;
 
@@ -513,4 +513,41 @@ describe("Conversion tests", function() {
        assert.deepEqual(back, data);
    });
 
+
+   it("test decoder", function() {
+       const decode = jsonDecoder({ prim: "int" });
+       assert.deepEqual(decode({ int: "1" }), "1");
+       assert.deepEqual(decode({ int: "-2" }), "-2");
+   });
+
+   it("test error", function() {
+       const sch = {
+           prim: "pair",
+           args: [
+             { prim: "address", annots: ["%owner"] },
+             { prim: "map", args: [{ prim: "address" }, { prim: "nat" }], annots: ["%balances"] },
+           ],
+       };
+       const catchError = (data) => {
+           try {
+               jsonEncode(sch, data);
+           } catch (err) {
+               return jsonError(sch, err);
+           }
+           assert.fail("no error");
+       };
+
+       const invalid = catchError({ owner: "tz1", balances: { MichelsonMap: {} } });
+       assert.equal(invalid.class, "AddressValidationError");
+       assert.deepEqual(invalid.path, ["owner"]);
+       assert.equal(invalid.field, "owner");
+       assert.equal(invalid.value, "tz1");
+
+       const key = catchError({
+           owner: "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb",
+           balances: { MichelsonMap: { tz1: "1" } },
+       });
+       assert.deepEqual(key.path, ["balances", "key"]);
+   });
+
 });
@@ -0,0 +1,243 @@
+//! The cases of `tests/mocha/conversion_tests.spec.js`, run against the native backend
+//! in both directions
+use serde_json::{json, Value};
+use zfx_michelson::micheline::Micheline;
+use zfx_michelson::native;
+
+const REGISTER_SCHEMA: &str = include_str!("./schema.json");
+
+fn michelson(s: &str) -> Micheline {
+    Micheline::parse_michelson(s).unwrap()
+}
+
+fn check(schema: &Micheline, encoded: &Micheline, data: Value) {
+    let decoded = native::decode(encoded, schema).unwrap();
+    assert_eq!(decoded, data);
+    let reencoded = native::encode(&data, schema).unwrap();
+    assert_eq!(&reencoded, encoded);
+}
+
+#[test]
+fn test_unit() {
+    check(
+        &michelson("unit %a"),
+        &michelson("Unit"),
+        json!({ "__unit__": null }),
+    );
+}
+
+#[test]
+fn test_map() {
+    check(
+        &michelson("map string int"),
+        &michelson(r#"{ Elt "field" 1 }"#),
+        json!({ "MichelsonMap": { "field": "1" } }),
+    );
+}
+
+#[test]
+fn test_enum() {
+    check(
+        &michelson("or (unit %aaA) (unit %ccC)"),
+        &michelson("Left Unit"),
+        json!({ "__enum__": "AaA" }),
+    );
+}
+
+#[test]
+fn test_register_storage() {
+    let schema = Micheline::from_str(REGISTER_SCHEMA).unwrap();
+    let encoded = michelson(
+        r#"Pair (Pair (Pair {} {}) (Pair "tz1burnburnburnburnburnburnburjAYjjX" (Left (Left Unit))))
+                (Pair { Elt "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                            (Pair (Pair "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                                        "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg")
+                                  0x) }
+                      { "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c" })"#,
+    );
+    let data = json!({
+        "state": { "__enum__": "Genesis" },
+        "owner": "tz1burnburnburnburnburnburnburjAYjjX",
+        "validators": ["tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"],
+        "old_validators": [],
+        "old_validator_map": { "MichelsonMap": {} },
+        "validator_map": {
+            "MichelsonMap": {
+                "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c": {
+                    "baking_account": "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c",
+                    "public_key": "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg",
+                    "tls_cert": "",
+                },
+            },
+        },
+    });
+    check(&schema, &encoded, data);
+}
+
+#[test]
+fn test_register_schema_with_history() {
+    let schema = Micheline::from_str(REGISTER_SCHEMA).unwrap();
+    let encoded = michelson(
+        r#"Pair (Pair (Pair { Elt "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                                  { Pair (Pair (Pair "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                                                     "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg")
+                                               0x)
+                                         "2022-09-23T00:00:00.000Z" } }
+                            { "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c" })
+                      (Pair "tz1burnburnburnburnburnburnburjAYjjX" (Left (Left Unit))))
+                (Pair { Elt "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                            (Pair (Pair "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"
+                                        "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg")
+                                  0x) }
+                      { "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c" })"#,
+    );
+    let data = json!({
+        "state": { "__enum__": "Genesis" },
+        "owner": "tz1burnburnburnburnburnburnburjAYjjX",
+        "validators": ["tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"],
+        "old_validators": ["tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c"],
+        "old_validator_map": {
+            "MichelsonMap": {
+                "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c": [{
+                    "baking_account": "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c",
+                    "public_key": "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg",
+                    "tls_cert": "",
+                    "3": "2022-09-23T00:00:00.000Z",
+                }],
+            },
+        },
+        "validator_map": {
+            "MichelsonMap": {
+                "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c": {
+                    "baking_account": "tz1d8LSBpEsLtLkCmaj2yBdv2xF4wSYNAa8c",
+                    "public_key": "edpku2tvek7QFRYm12819P8RwSY8m7zSzKV9RMnWHy3xVbrBwN5zAg",
+                    "tls_cert": "",
+                },
+            },
+        },
+    });
+    check(&schema, &encoded, data);
+}
+
+#[test]
+fn test_enum_with_parameter() {
+    check(
+        &michelson("or (or (int %decrement) (int %increment)) (unit %reset)"),
+        &michelson("Left (Left 1)"),
+        json!({ "decrement": "1" }),
+    );
+}
+
+#[test]
+fn test_enum_with_parameter_in_record() {
+    let schema =
+        michelson("pair (or %a (or (int %decrement) (int %increment)) (unit %reset)) (int %i)");
+    check(
+        &schema,
+        &michelson("Pair (Left (Left 1)) 42"),
+        json!({ "a": { "decrement": "1" }, "i": "42" }),
+    );
+    check(
+        &schema,
+        &michelson("Pair (Right Unit) 42"),
+        json!({ "a": { "__enum__": "Reset" }, "i": "42" }),
+    );
+}
+
+#[test]
+fn test_record_in_record() {
+    check(
+        &michelson("pair (int %a) (pair %b (int %c) (int %d))"),
+        &michelson("Pair 0 (Pair 2 3)"),
+        json!({ "a": "0", "b": { "c": "2", "d": "3" } }),
+    );
+}
+
+#[test]
+fn test_adt() {
+    let schema = michelson(
+        "pair (or %a (or (pair %decrement int string) (pair %increment int int)) (unit %reset))
+              (int %i)",
+    );
+    check(
+        &schema,
+        &michelson(r#"Pair (Left (Left (Pair 1 "foo"))) 42"#),
+        json!({ "a": { "decrement": { "0": "1", "1": "foo" } }, "i": "42" }),
+    );
+}
+
+#[test]
+fn test_option() {
+    let schema = michelson("option %a int");
+    check(&schema, &michelson("Some 1"), json!("1"));
+    check(&schema, &michelson("None"), json!(null));
+}
+
+#[test]
+fn test_option_in_record() {
+    let schema = michelson("pair (option %a int) (int %b)");
+    check(
+        &schema,
+        &michelson("Pair (Some 1) 1"),
+        json!({ "a": "1", "b": "1" }),
+    );
+    check(
+        &schema,
+        &michelson("Pair None 1"),
+        json!({ "a": null, "b": "1" }),
+    );
+}
+
+#[test]
+fn test_bool() {
+    check(&michelson("bool"), &michelson("True"), json!(true));
+}
+
+#[test]
+fn test_decode_fixture() {
+    let schema = Micheline::from_str(REGISTER_SCHEMA).unwrap();
+    let encoded = Micheline::from_str(include_str!("./michelson-to-decode.json")).unwrap();
+    let decoded = native::decode(&encoded, &schema).unwrap();
+    let expected = json!({
+        "old_validator_map": "4",
+        "old_validators": [],
+        "owner": "tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb",
+        "state": { "__enum__": "Sealed" },
+        "validator_map": "5",
+        "validators": ["tz1VSUr8wwNhLAzempoch5d6hLRiTh8Cjcjb"],
+    });
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn test_encode_big_map_id() {
+    let schema = Micheline::from_str(REGISTER_SCHEMA).unwrap();
+    let encoded = Micheline::from_str(include_str!("./michelson-to-decode.json")).unwrap();
+    let decoded = native::decode(&encoded, &schema).unwrap();
+    let reencoded = native::encode(&decoded, &schema).unwrap();
+    // The fixture uses the comb notation for pairs, compare after a roundtrip
+    assert_eq!(native::decode(&reencoded, &schema).unwrap(), decoded);
+}
+
+/// The documented deviations from Taquito, for values that none of the fixtures above contain
+#[test]
+fn test_deviations() {
+    // Maps nested in lists are wrapped
+    check(
+        &michelson("list (map string int)"),
+        &michelson(r#"{ { Elt "a" 1 } }"#),
+        json!([{ "MichelsonMap": { "a": "1" } }]),
+    );
+    // Non-scalar keys are serialised as JSON
+    check(
+        &michelson("map (pair (int %x) (int %y)) string"),
+        &michelson(r#"{ Elt (Pair 1 2) "a" }"#),
+        json!({ "MichelsonMap": { r#"{"x":"1","y":"2"}"#: "a" } }),
+    );
+    // Only `or` variants are enums
+    check(
+        &michelson("map string unit"),
+        &michelson(r#"{ Elt "a" Unit }"#),
+        json!({ "MichelsonMap": { "a": { "__unit__": null } } }),
+    );
+}
@@ -1,11 +1,12 @@
+#![cfg(feature = "async-tokio")]
 use serde_json::Value;
 use std::fs::File;
 use zfx_michelson::michelson::*;
 
 #[tokio::test]
 async fn happy_decode() {
-    zfx_michelson::michelson::install_parser().await;
-    let mut p = Parser::new();
+    zfx_michelson::michelson::install_parser().await.unwrap();
+    let p = Parser::new();
 
     let schema_file = File::open("tests/schema.json").expect("schema file should open read only");
     let schema: Value = serde_json::from_reader(schema_file).expect("file should be proper JSON");
@@ -21,3 +22,83 @@ async fn happy_decode() {
         _ => panic!("unexpected decode result"),
     }
 }
+
+#[tokio::test]
+async fn registered_decode() {
+    zfx_michelson::michelson::install_parser().await.unwrap();
+    let p = Parser::new();
+
+    let schema_file = File::open("tests/schema.json").expect("schema file should open read only");
+    let schema: Value = serde_json::from_reader(schema_file).expect("file should be proper JSON");
+    let michelson_file =
+        File::open("tests/michelson-to-decode.json").expect("michelson file should open read only");
+    let michelson: Value =
+        serde_json::from_reader(michelson_file).expect("file should be proper JSON");
+
+    let expected = p.decode(michelson.clone(), schema.clone()).await.unwrap();
+    let handle = p.register_schema(schema).await.unwrap();
+    for _ in 0..3 {
+        let decoded = p
+            .decode_registered(michelson.clone(), handle)
+            .await
+            .unwrap();
+        assert_eq!(decoded, expected);
+    }
+    assert!(p.unregister_schema(handle).await.unwrap());
+    let result = p.decode_registered(michelson, handle).await;
+    assert!(matches!(result, Err(zfx_michelson::Error::UnknownSchema)));
+}
+
+#[tokio::test]
+async fn pool_concurrent_decode() {
+    zfx_michelson::michelson::install_parser().await.unwrap();
+    let pool = zfx_michelson::ParserPool::new(2, 16);
+    let schema: Value = serde_json::from_str(r#"{ "prim": "int" }"#).unwrap();
+
+    let mut handles = vec![];
+    for i in 0..8 {
+        let pool = pool.clone();
+        let schema = schema.clone();
+        handles.push(tokio::spawn(async move {
+            let michelson = serde_json::json!({ "int": i.to_string() });
+            pool.decode(michelson, schema).await.unwrap()
+        }));
+    }
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.await.unwrap(), Value::String(i.to_string()));
+    }
+    let stats = pool.stats();
+    assert_eq!((stats.workers, stats.busy_workers, stats.queued), (2, 0, 0));
+}
+
+#[tokio::test]
+async fn parser_concurrent_requests() {
+    zfx_michelson::michelson::install_parser().await.unwrap();
+    let p = Parser::new();
+    let schema: Value = serde_json::from_str(r#"{ "prim": "int" }"#).unwrap();
+
+    let mut handles = vec![];
+    for i in 0..16 {
+        let p = p.clone();
+        let schema = schema.clone();
+        handles.push(tokio::spawn(async move {
+            let michelson = serde_json::json!({ "int": i.to_string() });
+            p.decode(michelson, schema).await.unwrap()
+        }));
+    }
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.await.unwrap(), Value::String(i.to_string()));
+    }
+}
+
+#[tokio::test]
+async fn supervised_decode() {
+    use zfx_michelson::{SupervisedParser, SupervisorConfig};
+
+    zfx_michelson::michelson::install_parser().await.unwrap();
+    let p = SupervisedParser::new(SupervisorConfig::default()).unwrap();
+    let schema: Value = serde_json::from_str(r#"{ "prim": "int" }"#).unwrap();
+    let michelson: Value = serde_json::from_str(r#"{ "int": "-26" }"#).unwrap();
+    let decoded = p.decode(michelson, schema).await.unwrap();
+    assert_eq!(decoded, Value::String("-26".to_owned()));
+}